@@ -0,0 +1,115 @@
+use crate::error::Error;
+use crate::outcome::{Outcome, Status};
+use crate::test_item::TestItem;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The saved result of a single test
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    pub status: String,
+    pub info: Option<String>,
+}
+
+impl BaselineEntry {
+    fn new(outcome: &Outcome) -> BaselineEntry {
+        BaselineEntry {
+            status: outcome.status.as_str().to_owned(),
+            info: outcome.info.clone(),
+        }
+    }
+
+    fn passed(&self) -> bool {
+        self.status == Status::Pass.as_str()
+    }
+}
+
+/// The results of a run, saved so that later runs can be compared against it.
+/// Tests are keyed by name (e.g. "FindById").
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    pub results: BTreeMap<String, BaselineEntry>,
+}
+
+/// A test whose result differs from the baseline
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub test_item: TestItem,
+    pub before: Option<BaselineEntry>,
+    pub after: BaselineEntry,
+}
+
+/// How a run differs from a baseline
+#[derive(Debug, Clone, Default)]
+pub struct Comparison {
+    /// Passed in the baseline but not now
+    pub newly_failing: Vec<Change>,
+
+    /// Passes now but did not in the baseline (or was not in it)
+    pub newly_passing: Vec<Change>,
+
+    /// Any other change in status or info
+    pub changed: Vec<Change>,
+}
+
+impl Baseline {
+    pub fn from_results(results: &[(TestItem, Outcome)]) -> Baseline {
+        Baseline {
+            results: results
+                .iter()
+                .map(|(t, o)| (t.id().to_owned(), BaselineEntry::new(o)))
+                .collect(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Baseline, Error> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    pub fn compare(&self, results: &[(TestItem, Outcome)]) -> Comparison {
+        let mut comparison = Comparison::default();
+
+        for (test_item, outcome) in results {
+            let before = self.results.get(test_item.id()).cloned();
+            let after = BaselineEntry::new(outcome);
+            if before.as_ref() == Some(&after) {
+                continue;
+            }
+
+            let was_passing = before.as_ref().map(|b| b.passed()).unwrap_or(false);
+            let is_passing = after.passed();
+            let change = Change {
+                test_item: *test_item,
+                before,
+                after,
+            };
+
+            match (was_passing, is_passing) {
+                (true, false) => comparison.newly_failing.push(change),
+                (false, true) => comparison.newly_passing.push(change),
+                _ => comparison.changed.push(change),
+            }
+        }
+
+        comparison
+    }
+}
+
+impl Comparison {
+    /// Newly failing tests that should fail the run. Optional tests only
+    /// count if `include_optional` is set.
+    pub fn regressions(&self, include_optional: bool) -> usize {
+        self.newly_failing
+            .iter()
+            .filter(|c| include_optional || c.test_item.required())
+            .count()
+    }
+}
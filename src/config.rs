@@ -0,0 +1,184 @@
+use crate::error::Error;
+use crate::registry::Registry;
+use crate::test_item::TestItem;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Run configuration, loaded from a JSON file with `--config=<file>` and
+/// then overridden by any command line options.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The relay to test, e.g. "ws://localhost:8080"
+    pub relay_url: Option<String>,
+
+    /// The nsec of the first registered user
+    pub nsec1: Option<String>,
+
+    /// The nsec of the second registered user
+    pub nsec2: Option<String>,
+
+    pub timeouts: Timeouts,
+
+    /// Tests (by name, e.g. "FindById") and whether they are expected to pass.
+    /// A test behaving as expected does not count against the exit status.
+    pub expected: BTreeMap<String, Expectation>,
+
+    /// Quiet except for the machine-readable report
+    pub script: bool,
+
+    /// Report format: "json", "junit" or "tap"
+    pub format: Option<String>,
+
+    /// File to write the report to, instead of stdout
+    pub output: Option<String>,
+
+    /// Baseline file to compare this run against
+    pub baseline: Option<String>,
+
+    /// File to save this run's results to, as a baseline for later runs
+    pub save_baseline: Option<String>,
+
+    /// Directory to write the wire transcript of each failing test to, as
+    /// `<TestId>.txt`
+    pub transcript_dir: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Expectation {
+    Pass,
+    Fail,
+}
+
+/// All timeouts, in milliseconds
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    /// How long to wait for the relay to respond to a message
+    pub wait_ms: u64,
+
+    /// How long to wait before reconnecting, and after being disconnected
+    pub reconnect_delay_ms: u64,
+
+    /// How long to wait for a websocket connection to be established
+    pub connect_ms: u64,
+
+    /// How long to wait for the handshake to settle after reconnecting
+    pub handshake_settle_ms: u64,
+
+    /// How long to wait for a response to AUTH
+    pub auth_ms: u64,
+
+    /// How long to wait for the NIP-11 document
+    pub nip11_ms: u64,
+
+    /// How long to hold a subscription idle, checking the relay keeps it
+    pub idle_ms: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Timeouts {
+        Timeouts {
+            wait_ms: 2000,
+            reconnect_delay_ms: 3000,
+            connect_ms: 5000,
+            handshake_settle_ms: 250,
+            auth_ms: 1000,
+            nip11_ms: 60000,
+            idle_ms: 30000,
+        }
+    }
+}
+
+impl Timeouts {
+    pub fn wait(&self) -> Duration {
+        Duration::from_millis(self.wait_ms)
+    }
+
+    pub fn reconnect_delay(&self) -> Duration {
+        Duration::from_millis(self.reconnect_delay_ms)
+    }
+
+    pub fn connect(&self) -> Duration {
+        Duration::from_millis(self.connect_ms)
+    }
+
+    pub fn handshake_settle(&self) -> Duration {
+        Duration::from_millis(self.handshake_settle_ms)
+    }
+
+    pub fn auth(&self) -> Duration {
+        Duration::from_millis(self.auth_ms)
+    }
+
+    pub fn nip11(&self) -> Duration {
+        Duration::from_millis(self.nip11_ms)
+    }
+
+    pub fn idle(&self) -> Duration {
+        Duration::from_millis(self.idle_ms)
+    }
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, Error> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let config: Config = serde_json::from_str(&contents)
+            .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+        Ok(config)
+    }
+
+    /// Catch misspelled test names, rather than silently ignoring them
+    pub fn check_expected(&self, registry: &Registry) -> Result<(), Error> {
+        for name in self.expected.keys() {
+            if registry.get(name).is_none() {
+                return Err(Error::Config(format!("Unknown test in expected: {}", name)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the test is expected to pass, if the configuration says
+    pub fn expectation(&self, test_item: TestItem) -> Option<Expectation> {
+        self.expected.get(test_item.id()).copied()
+    }
+
+    /// Apply a command line override such as `--wait-ms=500`.
+    /// Returns Ok(false) if the option is not a configuration option.
+    pub fn apply_override(&mut self, arg: &str) -> Result<bool, Error> {
+        let (key, value) = match arg.strip_prefix("--").and_then(|a| a.split_once('=')) {
+            Some(kv) => kv,
+            None => return Ok(false),
+        };
+
+        let millis = |v: &str| -> Result<u64, Error> {
+            v.parse::<u64>()
+                .map_err(|_| Error::Config(format!("--{} expects milliseconds, got {}", key, v)))
+        };
+
+        match key {
+            "relay-url" => self.relay_url = Some(value.to_owned()),
+            "nsec1" => self.nsec1 = Some(value.to_owned()),
+            "nsec2" => self.nsec2 = Some(value.to_owned()),
+            "wait-ms" => self.timeouts.wait_ms = millis(value)?,
+            "reconnect-delay-ms" => self.timeouts.reconnect_delay_ms = millis(value)?,
+            "connect-timeout-ms" => self.timeouts.connect_ms = millis(value)?,
+            "handshake-settle-ms" => self.timeouts.handshake_settle_ms = millis(value)?,
+            "auth-timeout-ms" => self.timeouts.auth_ms = millis(value)?,
+            "nip11-timeout-ms" => self.timeouts.nip11_ms = millis(value)?,
+            "idle-ms" => self.timeouts.idle_ms = millis(value)?,
+            "format" => self.format = Some(value.to_owned()),
+            "output" => self.output = Some(value.to_owned()),
+            "baseline" => self.baseline = Some(value.to_owned()),
+            "save-baseline" => self.save_baseline = Some(value.to_owned()),
+            "transcript-dir" => self.transcript_dir = Some(value.to_owned()),
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
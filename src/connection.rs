@@ -1,16 +1,23 @@
+use crate::config::Timeouts;
+use crate::context::{make_event, EventParts};
 use crate::error::Error;
-use crate::globals::{EventParts, Globals, User, GLOBALS};
+use crate::transcript::{Direction, Transcript};
 use base64::Engine;
 use colorful::{Color, Colorful};
 use futures_util::{SinkExt, StreamExt};
-use http::Uri;
-use nostr_types::{ClientMessage, Event, EventKind, Filter, Id, RelayMessage, SubscriptionId, Tag};
+use http::{HeaderMap, Uri};
+use nostr_types::{
+    ClientMessage, Event, EventKind, Filter, Id, KeySigner, RelayMessage, SubscriptionId, Tag,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
+use strum_macros::{AsRefStr, EnumString};
 use tungstenite::Message;
 
-const WAIT_SECONDS: u64 = 3;
-
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum AuthState {
     #[default]
@@ -41,6 +48,11 @@ pub struct FetchResult {
 }
 
 impl FetchResult {
+    /// The parsed CLOSED message, if the relay closed the subscription
+    pub fn close_reason(&self) -> Option<Reason> {
+        self.close_msg.as_deref().map(Reason::parse)
+    }
+
     pub fn into_events(self) -> Vec<Event> {
         let mut v: Vec<Event> = self.pre_eose_events;
         if let Some(post) = self.post_eose_events {
@@ -50,17 +62,114 @@ impl FetchResult {
     }
 }
 
+/// A relay's reply to COUNT (NIP-45)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Count {
+    pub count: u64,
+
+    #[serde(default)]
+    pub approximate: bool,
+
+    // HyperLogLog registers, hex encoded, if the relay sent them
+    #[serde(default)]
+    pub hll: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct CountResult {
+    // The relay's count. If None, it closed the request or we timed out
+    pub count: Option<Count>,
+
+    // If the relay closed the request, this is the message
+    pub close_msg: Option<String>,
+}
+
+/// The machine-readable prefixes NIP-01 defines for OK and CLOSED messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ReasonPrefix {
+    Duplicate,
+    Pow,
+    Blocked,
+    RateLimited,
+    Invalid,
+    Restricted,
+    Mute,
+    Error,
+    AuthRequired,
+    Unsupported,
+}
+
+/// The message of an OK or CLOSED, split into its prefix and the rest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reason {
+    // None if the message has no prefix, or one NIP-01 does not define
+    pub prefix: Option<ReasonPrefix>,
+
+    // The human-readable rest of the message, or all of it if there is no
+    // standard prefix
+    pub message: String,
+}
+
+impl Reason {
+    pub fn parse(s: &str) -> Reason {
+        if let Some((prefix, rest)) = s.split_once(':') {
+            if let Ok(prefix) = ReasonPrefix::from_str(prefix) {
+                return Reason {
+                    prefix: Some(prefix),
+                    message: rest.trim_start().to_owned(),
+                };
+            }
+        }
+        Reason {
+            prefix: None,
+            message: s.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.prefix {
+            Some(prefix) => write!(f, "{}: {}", prefix.as_ref(), self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// A message from the relay, including those nostr-types does not know
+enum Incoming {
+    Relay(RelayMessage),
+    Count(SubscriptionId, Count),
+}
+
 #[derive(Debug)]
 pub struct Connection {
     pub relay_url: String,
     pub websocket: Ws,
     pub auth_state: AuthState,
     pub dup_auth: bool,
+
+    // The latest AUTH challenge, whatever has happened since
+    pub challenge: Option<String>,
+
     pub next_sub_id: AtomicUsize,
+    pub timeouts: Timeouts,
+    pub disconnected: bool,
+
+    // Whether the relay has ever answered an EVENT with an OK
+    pub saw_ok_after_event: bool,
+
+    // Frames sent and received since the transcript was last taken
+    pub transcript: Transcript,
 }
 
 impl Connection {
-    pub async fn new(relay_url: String, next_sub_id: usize) -> Result<Connection, Error> {
+    pub async fn new(
+        relay_url: String,
+        next_sub_id: usize,
+        timeouts: Timeouts,
+    ) -> Result<Connection, Error> {
         let (host, uri) = url_to_host_and_uri(&relay_url);
         let key: [u8; 16] = rand::random();
         let request = http::request::Request::builder()
@@ -77,7 +186,7 @@ impl Connection {
             .body(())?;
 
         let (websocket, _response) = tokio::time::timeout(
-            Duration::new(5, 0),
+            timeouts.connect(),
             tokio_tungstenite::connect_async(request),
         )
         .await??;
@@ -87,7 +196,12 @@ impl Connection {
             websocket,
             auth_state: AuthState::NotYetRequested,
             dup_auth: false,
+            challenge: None,
             next_sub_id: AtomicUsize::new(next_sub_id),
+            timeouts,
+            disconnected: false,
+            saw_ok_after_event: false,
+            transcript: Transcript::default(),
         })
     }
 
@@ -96,7 +210,7 @@ impl Connection {
         log!("    {}", "*** RECONNECTING ***".color(Color::Red));
 
         // Wait for a few seconds before reconnecting
-        tokio::time::sleep(Duration::from_secs(WAIT_SECONDS)).await;
+        tokio::time::sleep(self.timeouts.reconnect_delay()).await;
 
         let (host, uri) = url_to_host_and_uri(&self.relay_url);
         let key: [u8; 16] = rand::random();
@@ -114,20 +228,21 @@ impl Connection {
             .body(())?;
 
         let (websocket, _response) = tokio::time::timeout(
-            Duration::new(5, 0),
+            self.timeouts.connect(),
             tokio_tungstenite::connect_async(request),
         )
         .await??;
 
         // Sleep a bit for the handshake to finish, else we can end up with
         // "Websocket: WebSocket protocol error: Handshake not finished"
-        tokio::time::sleep(Duration::from_millis(250)).await;
+        tokio::time::sleep(self.timeouts.handshake_settle()).await;
 
-        GLOBALS.disconnected.store(false, Ordering::Relaxed);
+        self.disconnected = false;
 
         self.websocket = websocket;
         self.auth_state = AuthState::NotYetRequested;
         self.dup_auth = false;
+        self.challenge = None;
         self.next_sub_id = AtomicUsize::new(0);
 
         Ok(())
@@ -137,23 +252,36 @@ impl Connection {
         let msg = Message::Close(None);
         log!("    {} CLOSING", "-->".color(Color::Khaki1));
         let _ = self.inner_send_message(msg).await;
-        GLOBALS.disconnected.store(true, Ordering::Relaxed);
+        self.disconnected = true;
         Ok(())
     }
 
     async fn inner_send_message(&mut self, msg: tungstenite::Message) -> Result<(), Error> {
-        if GLOBALS.disconnected.load(Ordering::Relaxed) {
+        if self.disconnected {
             self.reconnect().await?;
         }
 
+        self.transcript.record(Direction::Sent, describe(&msg));
+
         if let Err(e) = self.websocket.send(msg).await {
-            GLOBALS.disconnected.store(true, Ordering::Relaxed);
+            self.disconnected = true;
             Err(e)?
         } else {
             Ok(())
         }
     }
 
+    /// A subscription id not used before on this connection
+    pub fn fresh_sub_id(&self) -> SubscriptionId {
+        let sub_id_usize = self.next_sub_id.fetch_add(1, Ordering::Relaxed);
+        SubscriptionId(format!("sub{}", sub_id_usize))
+    }
+
+    /// Take the frames recorded so far, starting a new transcript
+    pub fn take_transcript(&mut self) -> Transcript {
+        std::mem::take(&mut self.transcript)
+    }
+
     pub async fn send_message(&mut self, message: ClientMessage) -> Result<(), Error> {
         let wire = serde_json::to_string(&message)?;
         let msg = Message::Text(wire);
@@ -163,10 +291,28 @@ impl Connection {
         Ok(())
     }
 
+    /// Send text as is, e.g. JSON that nostr-types can't (or won't) produce
+    pub async fn send_raw_message(&mut self, wire: String) -> Result<(), Error> {
+        let msg = Message::Text(wire);
+        log!("    {} {msg}", "-->".color(Color::Khaki1));
+        self.inner_send_message(msg).await
+    }
+
     pub async fn wait_for_message(
         &mut self,
         timeout: Duration,
     ) -> Result<Option<RelayMessage>, Error> {
+        loop {
+            match self.wait_for_incoming(timeout).await? {
+                None => return Ok(None),
+                Some(Incoming::Relay(message)) => return Ok(Some(message)),
+                // A late reply to a COUNT we are no longer waiting for
+                Some(Incoming::Count(_, _)) => continue,
+            }
+        }
+    }
+
+    async fn wait_for_incoming(&mut self, timeout: Duration) -> Result<Option<Incoming>, Error> {
         let mut timeout = tokio::time::interval(timeout);
         timeout.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
         timeout.tick().await; // use up the first immediate tick.
@@ -180,22 +326,28 @@ impl Connection {
                     let message = match message {
                         Some(m) => m,
                         None => {
-                            GLOBALS.disconnected.store(true, Ordering::Relaxed);
-                            tokio::time::sleep(Duration::from_secs(WAIT_SECONDS)).await;
+                            self.disconnected = true;
+                            tokio::time::sleep(self.timeouts.reconnect_delay()).await;
                             return Err(Error::Disconnected);
                         }
                     }?;
 
-                    // Self::display(message.clone())?;
+                    self.transcript.record(Direction::Received, describe(&message));
 
                     // Take action
                     match message {
                         Message::Text(s) => {
                             log!("    {} {s}", "<--".color(Color::MediumPurple2a));
+
+                            if let Some((sub, count)) = parse_count(&s) {
+                                return Ok(Some(Incoming::Count(sub, count)));
+                            }
+
                             let output: RelayMessage = serde_json::from_str(&s)?;
 
                             match output {
                                 RelayMessage::Auth(challenge) => {
+                                    self.challenge = Some(challenge.clone());
                                     match self.auth_state {
                                         AuthState::NotYetRequested => self.auth_state = AuthState::Challenged(challenge),
                                         _ => self.dup_auth = true,
@@ -205,7 +357,7 @@ impl Connection {
                                     continue;
                                 },
                                 RelayMessage::Ok(id, is_ok, ref reason) => {
-                                    GLOBALS.saw_ok_after_event.store(true, Ordering::Relaxed);
+                                    self.saw_ok_after_event = true;
                                     if let AuthState::InProgress(sent_id) = self.auth_state {
                                         if id == sent_id {
                                             self.auth_state = if is_ok {
@@ -222,14 +374,14 @@ impl Connection {
                                 _ => { }
                             }
 
-                            return Ok(Some(output));
+                            return Ok(Some(Incoming::Relay(output)));
                         },
                         Message::Binary(_) => { },
                         Message::Ping(_) => { },
                         Message::Pong(_) => { },
                         Message::Close(_) => {
-                            GLOBALS.disconnected.store(true, Ordering::Relaxed);
-                            tokio::time::sleep(Duration::from_secs(WAIT_SECONDS)).await;
+                            self.disconnected = true;
+                            tokio::time::sleep(self.timeouts.reconnect_delay()).await;
                             return Err(Error::Disconnected);
                         },
                         Message::Frame(_) => unreachable!(),
@@ -239,23 +391,10 @@ impl Connection {
         }
     }
 
-    pub async fn authenticate_if_challenged(&mut self, user: User) -> Result<(), Error> {
+    pub async fn authenticate_if_challenged(&mut self, signer: &KeySigner) -> Result<(), Error> {
         if let AuthState::Challenged(challenge) = &self.auth_state {
-            let event = Globals::make_event(
-                EventParts::Basic(
-                    EventKind::Auth,
-                    vec![
-                        Tag::new(&["relay", &self.relay_url]),
-                        Tag::new(&["challenge", challenge]),
-                    ],
-                    "".to_string(),
-                ),
-                user,
-            )?;
-            self.auth_state = AuthState::InProgress(event.id);
-            self.send_message(ClientMessage::Auth(Box::new(event)))
-                .await?;
-            let _ = self.wait_for_message(Duration::from_secs(1)).await?; // to await response
+            let challenge = challenge.clone();
+            self.authenticate(&challenge, signer).await?;
         }
         Ok(())
     }
@@ -268,25 +407,58 @@ impl Connection {
             self.auth_state = AuthState::InProgress(event.id);
             self.send_message(ClientMessage::Auth(Box::new(event)))
                 .await?;
-            let _ = self.wait_for_message(Duration::from_secs(1)).await?; // to await response
+            let _ = self.wait_for_message(self.timeouts.auth()).await?; // to await response
+        }
+        Ok(())
+    }
+
+    /// AUTH as the signer in answer to the latest challenge, even if this
+    /// connection has already authenticated (as someone else, say)
+    pub async fn authenticate_again(&mut self, signer: &KeySigner) -> Result<(), Error> {
+        if let Some(challenge) = self.challenge.clone() {
+            self.authenticate(&challenge, signer).await?;
         }
         Ok(())
     }
 
-    pub async fn trigger_auth_get_challenge(&mut self) -> Result<Option<String>, Error> {
+    // Answer the challenge with an AUTH by the signer, and await the OK
+    async fn authenticate(&mut self, challenge: &str, signer: &KeySigner) -> Result<(), Error> {
+        let event = make_event(
+            EventParts::Basic(
+                EventKind::Auth,
+                vec![
+                    Tag::new(&["relay", &self.relay_url]),
+                    Tag::new(&["challenge", challenge]),
+                ],
+                "".to_string(),
+            ),
+            signer,
+        )?;
+        self.auth_state = AuthState::InProgress(event.id);
+        self.send_message(ClientMessage::Auth(Box::new(event)))
+            .await?;
+        let _ = self.wait_for_message(self.timeouts.auth()).await?; // to await response
+        Ok(())
+    }
+
+    /// Try to provoke an AUTH challenge by posting a GiftWrap as the given
+    /// (unauthenticated) user
+    pub async fn trigger_auth_get_challenge(
+        &mut self,
+        signer: &KeySigner,
+    ) -> Result<Option<String>, Error> {
         if let AuthState::Challenged(challenge) = &self.auth_state {
             Ok(Some(challenge.to_owned()))
         } else {
             // Attempt to post a GiftWrap
-            let event = Globals::make_event(
+            let event = make_event(
                 EventParts::Basic(EventKind::GiftWrap, vec![], "".to_string()),
-                User::Stranger,
+                signer,
             )?;
-            self.post_event(event, Duration::from_secs(crate::WAIT))
-                .await?;
+            self.post_event(event, self.timeouts.wait()).await?;
 
             // Wait
-            let _ = self.wait_for_message(Duration::from_secs(1)).await?; // to await response
+            let _ = self.wait_for_message(self.timeouts.auth()).await?; // to await response
 
             if let AuthState::Challenged(challenge) = &self.auth_state {
                 Ok(Some(challenge.to_owned()))
@@ -312,17 +484,60 @@ impl Connection {
         self.fetch_events_inner(filter, timeout, false).await
     }
 
+    /// Fetch the events matching any of the filters, in a single REQ
+    pub async fn fetch_events_multi(
+        &mut self,
+        filters: Vec<Filter>,
+        timeout: Duration,
+    ) -> Result<FetchResult, Error> {
+        let sub_id = self.fresh_sub_id();
+
+        // nostr-types sends one filter per REQ, so build it ourselves
+        let mut parts: Vec<Value> = vec!["REQ".into(), sub_id.0.clone().into()];
+        for filter in filters.iter() {
+            parts.push(serde_json::to_value(filter)?);
+        }
+        self.send_raw_message(serde_json::to_string(&parts)?)
+            .await?;
+
+        self.receive_events(sub_id, timeout, true).await
+    }
+
     async fn fetch_events_inner(
         &mut self,
         filter: Filter,
         timeout: Duration,
         close: bool,
     ) -> Result<FetchResult, Error> {
-        let sub_id_usize = self.next_sub_id.fetch_add(1, Ordering::Relaxed);
-        let sub_id = SubscriptionId(format!("sub{}", sub_id_usize));
+        let sub_id = self.fresh_sub_id();
         let client_message = ClientMessage::Req(sub_id.clone(), filter);
         self.send_message(client_message).await?;
 
+        self.receive_events(sub_id, timeout, close).await
+    }
+
+    /// Like `fetch_events`, but with the filter given as JSON, so it may hold
+    /// fields nostr-types doesn't know (e.g. NIP-50 `search`)
+    pub async fn fetch_raw_events(
+        &mut self,
+        filter: Value,
+        timeout: Duration,
+    ) -> Result<FetchResult, Error> {
+        let sub_id = self.fresh_sub_id();
+        let wire = serde_json::to_string(&serde_json::json!(["REQ", sub_id.0, filter]))?;
+        self.send_raw_message(wire).await?;
+
+        self.receive_events(sub_id, timeout, true).await
+    }
+
+    /// Collect the events of a subscription until it is closed or goes
+    /// quiet. For REQs sent by hand, e.g. with `send_raw_message`.
+    pub async fn receive_events(
+        &mut self,
+        sub_id: SubscriptionId,
+        timeout: Duration,
+        close: bool,
+    ) -> Result<FetchResult, Error> {
         let mut pre_eose_events: Vec<Event> = Vec::new();
         let mut post_eose_events: Vec<Event> = Vec::new();
         let mut eose_happened: bool = false;
@@ -410,6 +625,47 @@ impl Connection {
         }
     }
 
+    /// Ask the relay to count the events matching any of the filters (NIP-45)
+    pub async fn count(
+        &mut self,
+        filters: Vec<Filter>,
+        timeout: Duration,
+    ) -> Result<CountResult, Error> {
+        let sub_id = self.fresh_sub_id();
+
+        // nostr-types has no COUNT message, so build it ourselves
+        let mut parts: Vec<Value> = vec!["COUNT".into(), sub_id.0.clone().into()];
+        for filter in filters.iter() {
+            parts.push(serde_json::to_value(filter)?);
+        }
+        self.send_raw_message(serde_json::to_string(&parts)?)
+            .await?;
+
+        loop {
+            match self.wait_for_incoming(timeout).await? {
+                None => {
+                    return Ok(CountResult {
+                        count: None,
+                        close_msg: None,
+                    })
+                }
+                Some(Incoming::Count(sub, count)) if sub == sub_id => {
+                    return Ok(CountResult {
+                        count: Some(count),
+                        close_msg: None,
+                    })
+                }
+                Some(Incoming::Relay(RelayMessage::Closed(sub, msg))) if sub == sub_id => {
+                    return Ok(CountResult {
+                        count: None,
+                        close_msg: Some(msg),
+                    })
+                }
+                Some(_) => continue,
+            }
+        }
+    }
+
     pub async fn close_subscription(&mut self, sub_id: SubscriptionId) -> Result<(), Error> {
         let client_message = ClientMessage::Close(sub_id);
         self.send_message(client_message).await?;
@@ -431,7 +687,7 @@ impl Connection {
                     if id != event_id {
                         continue;
                     }
-                    GLOBALS.saw_ok_after_event.store(true, Ordering::Relaxed);
+                    self.saw_ok_after_event = true;
                     return Ok((ok, msg));
                 }
                 Some(_) => continue,
@@ -446,9 +702,7 @@ impl Connection {
         timeout: Duration,
     ) -> Result<(bool, String), Error> {
         let wire = format!("[\"EVENT\",{}]", json);
-        let msg = Message::Text(wire);
-        log!("    {} {msg}", "-->".color(Color::Khaki1));
-        self.inner_send_message(msg).await?;
+        self.send_raw_message(wire).await?;
         loop {
             match self.wait_for_message(timeout).await? {
                 None => return Err(Error::TimedOut),
@@ -456,7 +710,7 @@ impl Connection {
                     if id != event_id {
                         continue;
                     }
-                    GLOBALS.saw_ok_after_event.store(true, Ordering::Relaxed);
+                    self.saw_ok_after_event = true;
                     return Ok((ok, msg));
                 }
                 Some(_) => continue,
@@ -478,13 +732,42 @@ pub fn url_to_host_and_uri(url: &str) -> (String, Uri) {
     (host.to_owned(), uri)
 }
 
-pub async fn fetch_nip11() -> Result<serde_json::Value, Error> {
+// Parse ["COUNT", <sub>, {"count": <n>, ...}]
+fn parse_count(s: &str) -> Option<(SubscriptionId, Count)> {
+    let array = match serde_json::from_str::<Value>(s).ok()? {
+        Value::Array(array) => array,
+        _ => return None,
+    };
+    if array.len() != 3 || array[0].as_str() != Some("COUNT") {
+        return None;
+    }
+    let sub = SubscriptionId(array[1].as_str()?.to_owned());
+    let count: Count = serde_json::from_value(array[2].clone()).ok()?;
+    Some((sub, count))
+}
+
+// How a frame appears in a transcript
+fn describe(msg: &Message) -> String {
+    match msg {
+        Message::Text(s) => s.to_owned(),
+        Message::Binary(b) => format!("BINARY ({} bytes)", b.len()),
+        Message::Ping(_) => "PING".to_owned(),
+        Message::Pong(_) => "PONG".to_owned(),
+        Message::Close(None) => "CLOSE".to_owned(),
+        Message::Close(Some(frame)) => format!("CLOSE {} {}", frame.code, frame.reason),
+        Message::Frame(_) => "FRAME".to_owned(),
+    }
+}
+
+/// Fetch the relay's NIP-11 document, along with the headers it was served with
+pub async fn fetch_nip11(
+    relay_url: &str,
+    timeout: Duration,
+) -> Result<(serde_json::Value, HeaderMap), Error> {
     use reqwest::redirect::Policy;
     use reqwest::Client;
-    use std::time::Duration;
 
-    let relay_url = GLOBALS.relay_url.read().clone();
-    let (host, uri) = url_to_host_and_uri(&relay_url);
+    let (host, uri) = url_to_host_and_uri(relay_url);
     let scheme = match uri.scheme() {
         Some(refscheme) => match refscheme.as_str() {
             "wss" => "https",
@@ -496,8 +779,8 @@ pub async fn fetch_nip11() -> Result<serde_json::Value, Error> {
     let url = format!("{}://{}{}", scheme, host, uri.path());
     let client = Client::builder()
         .redirect(Policy::none())
-        .connect_timeout(Duration::from_secs(60))
-        .timeout(Duration::from_secs(60))
+        .connect_timeout(timeout)
+        .timeout(timeout)
         .connection_verbose(true)
         .build()?;
     let response = client
@@ -506,7 +789,8 @@ pub async fn fetch_nip11() -> Result<serde_json::Value, Error> {
         .header("Accept", "application/nostr+json")
         .send()
         .await?;
+    let headers = response.headers().clone();
     let json = response.text().await?;
     let value: serde_json::Value = serde_json::from_str(&json)?;
-    Ok(value)
+    Ok((value, headers))
 }
@@ -0,0 +1,94 @@
+use crate::config::Timeouts;
+use crate::connection::Connection;
+use crate::context::User;
+use crate::error::Error;
+use crate::transcript::Transcript;
+use nostr_types::KeySigner;
+use std::collections::BTreeMap;
+
+/// Who a connection speaks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Never answers an AUTH challenge
+    Anonymous,
+
+    /// Authenticates as the user, if the relay asks
+    As(User),
+}
+
+/// Connections to the relay opened alongside the main one, by name. Each
+/// has its own identity and its own subscription counter, so a test can
+/// hold several sockets at once (e.g. one to subscribe and one to publish).
+///
+/// The runner closes them all at the end of each test, adding what was
+/// said on them to the test's transcript.
+#[derive(Debug)]
+pub struct Connections {
+    relay_url: String,
+    timeouts: Timeouts,
+    open: BTreeMap<String, Connection>,
+
+    // Frames from connections already closed during the test
+    closed: Transcript,
+}
+
+impl Connections {
+    pub fn new(relay_url: String, timeouts: Timeouts) -> Connections {
+        Connections {
+            relay_url,
+            timeouts,
+            open: BTreeMap::new(),
+            closed: Transcript::default(),
+        }
+    }
+
+    /// Open a connection under the name, replacing any already open by that
+    /// name. With a signer, answer any AUTH challenge as them.
+    pub async fn open(
+        &mut self,
+        name: &str,
+        signer: Option<&KeySigner>,
+    ) -> Result<&mut Connection, Error> {
+        self.close(name).await;
+
+        let mut connection = Connection::new(self.relay_url.clone(), 0, self.timeouts).await?;
+        if let Some(signer) = signer {
+            let _ = connection.wait_for_message(self.timeouts.auth()).await?;
+            connection.authenticate_if_challenged(signer).await?;
+        }
+
+        self.open.insert(name.to_owned(), connection);
+        Ok(self.get(name))
+    }
+
+    /// The connection open under the name. Panics if there isn't one, as
+    /// that is a mistake in the test.
+    pub(crate) fn get(&mut self, name: &str) -> &mut Connection {
+        match self.open.get_mut(name) {
+            Some(connection) => connection,
+            None => panic!("No connection named {}", name),
+        }
+    }
+
+    /// Close the connection open under the name, if there is one
+    pub async fn close(&mut self, name: &str) {
+        if let Some(mut connection) = self.open.remove(name) {
+            // If the relay already dropped it, sending a Close would reconnect
+            if !connection.disconnected {
+                let _ = connection.disconnect().await;
+            }
+            let mut transcript = connection.take_transcript();
+            transcript.label(name);
+            self.closed.merge(transcript);
+        }
+    }
+
+    /// Close every connection, returning all that was said on them
+    pub async fn close_all(&mut self) -> Transcript {
+        let names: Vec<String> = self.open.keys().cloned().collect();
+        for name in names {
+            self.close(&name).await;
+        }
+        std::mem::take(&mut self.closed)
+    }
+}
@@ -0,0 +1,182 @@
+use crate::config::{Config, Timeouts};
+use crate::connection::Connection;
+use crate::connections::{Connections, Role};
+use crate::error::Error;
+use crate::event_group::EventGroup;
+use crate::outcome::Outcome;
+use crate::test_item::TestItem;
+use colorful::{Color, Colorful};
+use nostr_types::{Event, EventKind, Id, KeySigner, PreEvent, PrivateKey, Signer, Tag, Unixtime};
+use std::collections::BTreeMap;
+
+/// Everything a single run against a relay needs. Each test is handed the
+/// context of the run it belongs to, so independent runs (e.g. against
+/// different relays) can proceed side by side in one process.
+pub struct RunContext {
+    pub config: Config,
+    pub relay_url: String,
+    pub connection: Connection,
+    pub connections: Connections,
+    pub stranger: KeySigner,
+    pub registered1: KeySigner,
+    pub registered2: KeySigner,
+    pub test_results: BTreeMap<TestItem, Outcome>,
+    pub nip11: Option<serde_json::Value>,
+    pub nip11_headers: Option<http::HeaderMap>,
+    pub event_group_a: EventGroup,
+    pub event_group_a_submitted: bool,
+    pub event_group_a_failed: bool,
+    pub search_group: EventGroup,
+    pub search_word: String,
+    pub search_group_submitted: bool,
+    pub search_group_failed: bool,
+}
+
+impl RunContext {
+    /// Connect to the relay named in the config
+    pub async fn new(config: Config) -> Result<RunContext, Error> {
+        let relay_url = match config.relay_url {
+            Some(ref u) => u.to_owned(),
+            None => return Err(Error::Config("No relay url".to_owned())),
+        };
+
+        let private_key1 = match config.nsec1 {
+            Some(ref s) => PrivateKey::try_from_bech32_string(s)?,
+            None => return Err(Error::Config("No nsec1".to_owned())),
+        };
+
+        let private_key2 = match config.nsec2 {
+            Some(ref s) => PrivateKey::try_from_bech32_string(s)?,
+            None => return Err(Error::Config("No nsec2".to_owned())),
+        };
+
+        log!("{}", "*** CONNECTING ***".color(Color::Red));
+        let connection = Connection::new(relay_url.clone(), 0, config.timeouts).await?;
+
+        Ok(RunContext {
+            connections: Connections::new(relay_url.clone(), config.timeouts),
+            config,
+            relay_url,
+            connection,
+            stranger: KeySigner::generate("stranger", 2)?,
+            registered1: KeySigner::from_private_key(private_key1, "", 8)?,
+            registered2: KeySigner::from_private_key(private_key2, "", 8)?,
+            test_results: BTreeMap::new(),
+            nip11: None,
+            nip11_headers: None,
+            event_group_a: EventGroup::new(),
+            event_group_a_submitted: false,
+            event_group_a_failed: false,
+            search_group: EventGroup::new(),
+            search_word: format!("relaytester{:08x}", rand::random::<u32>()),
+            search_group_submitted: false,
+            search_group_failed: false,
+        })
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        self.config.timeouts
+    }
+
+    pub fn signer(&self, user: User) -> &KeySigner {
+        match user {
+            User::Stranger => &self.stranger,
+            User::Registered1 => &self.registered1,
+            User::Registered2 => &self.registered2,
+        }
+    }
+
+    /// Open a connection alongside the main one (see [`Connections`])
+    pub async fn connect(&mut self, name: &str, role: Role) -> Result<&mut Connection, Error> {
+        let signer = match role {
+            Role::Anonymous => None,
+            Role::As(User::Stranger) => Some(&self.stranger),
+            Role::As(User::Registered1) => Some(&self.registered1),
+            Role::As(User::Registered2) => Some(&self.registered2),
+        };
+        self.connections.open(name, signer).await
+    }
+
+    pub fn make_event(&self, parts: EventParts, user: User) -> Result<Event, Error> {
+        make_event(parts, self.signer(user))
+    }
+
+    pub fn make_raw_event(
+        &self,
+        created_at: &str,
+        kind: &str,
+        tags: &str,
+        content: &str,
+        user: User,
+    ) -> (Id, String) {
+        let u = self.signer(user);
+
+        let public_key_hex = u.public_key().as_hex_string();
+
+        let serial_for_sig = format!(
+            "[0,\"{}\",{},{},{},\"{}\"]",
+            &public_key_hex, created_at, kind, tags, content
+        );
+        use secp256k1::hashes::Hash;
+        let hash = secp256k1::hashes::sha256::Hash::hash(serial_for_sig.as_bytes());
+        let id: [u8; 32] = hash.to_byte_array();
+        let id = Id(id);
+        let signature = u.sign_id(id).unwrap();
+
+        let raw_event = format!(
+            r##"{{"id":"{}","pubkey":"{}","created_at":{},"kind":{},"tags":{},"content":"{}","sig":"{}"}}"##,
+            id.as_hex_string(),
+            &public_key_hex,
+            created_at,
+            kind,
+            tags,
+            content,
+            signature.as_hex_string()
+        );
+
+        (id, raw_event)
+    }
+}
+
+/// Sign an event made from the parts
+pub fn make_event(parts: EventParts, signer: &KeySigner) -> Result<Event, Error> {
+    let (kind, tags, content, created_at) = match parts {
+        EventParts::Basic(k, t, c) => (k, t, c, Unixtime::now()),
+        EventParts::Dated(k, t, c, d) => (k, t, c, d),
+    };
+
+    let pre_event = PreEvent {
+        pubkey: signer.public_key(),
+        created_at,
+        kind,
+        tags,
+        content,
+    };
+
+    Ok(signer.sign_event(pre_event)?)
+}
+
+/// The proof of work (NIP-13) of an event id: its leading zero bits
+pub(crate) fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in bytes {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+#[derive(Debug, Clone)]
+pub enum EventParts {
+    Basic(EventKind, Vec<Tag>, String),
+    Dated(EventKind, Vec<Tag>, String, Unixtime),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum User {
+    Stranger,
+    Registered1,
+    Registered2,
+}
@@ -1,10 +1,9 @@
+use crate::connection::Connection;
+use crate::context::{make_event, EventParts};
 use crate::error::Error;
-use crate::globals::{EventParts, Globals, User, GLOBALS};
-use crate::WAIT;
-use nostr_types::Event;
+use nostr_types::{Event, KeySigner};
 use std::collections::HashMap;
 use std::slice::Iter;
-use std::time::Duration;
 
 pub struct EventGroup {
     // Events, and whether they can_read_back
@@ -24,20 +23,17 @@ impl EventGroup {
 
     pub async fn insert(
         &mut self,
+        connection: &mut Connection,
+        signer: &KeySigner,
         key: &'static str,
         parts: EventParts,
         can_read_back: bool,
     ) -> Result<(), Error> {
-        let event = Globals::make_event(parts.clone(), User::Registered1)?;
+        let event = make_event(parts.clone(), signer)?;
 
         // Submit to the relay
-        let (_ok, _reason) = GLOBALS
-            .connection
-            .write()
-            .as_mut()
-            .unwrap()
-            .post_event(event.clone(), Duration::from_secs(WAIT))
-            .await?;
+        let wait = connection.timeouts.wait();
+        let (_ok, _reason) = connection.post_event(event.clone(), wait).await?;
 
         // Insert into the event group
         let index = self.vec.len();
@@ -0,0 +1,72 @@
+//! A test suite for nostr relays.
+//!
+//! The `relay-tester` binary is a thin wrapper around this library. To run the
+//! tests from your own code (e.g. against a relay spawned in-process):
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), relay_tester::error::Error> {
+//! use relay_tester::config::Config;
+//! use relay_tester::runner::Runner;
+//!
+//! let config = Config {
+//!     relay_url: Some("ws://localhost:8080".to_owned()),
+//!     nsec1: Some("nsec1...".to_owned()),
+//!     nsec2: Some("nsec1...".to_owned()),
+//!     ..Default::default()
+//! };
+//! let run = Runner::new(config).run().await?;
+//! assert_eq!(run.summary.fail, 0);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Print to stderr, unless quiet (see [`set_quiet`])
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {{
+        if ! $crate::is_quiet() {
+            std::eprintln!($($arg)*);
+        }
+    }};
+}
+
+pub mod baseline;
+pub mod config;
+pub mod connection;
+pub mod connections;
+pub mod context;
+pub mod error;
+pub mod event_group;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod outcome;
+pub mod registry;
+pub mod report;
+pub mod runner;
+pub mod selection;
+pub mod stage;
+pub mod test_item;
+mod tests;
+pub mod transcript;
+
+pub use connection::Connection;
+pub use connections::{Connections, Role};
+pub use context::RunContext;
+pub use outcome::{Outcome, Status};
+pub use registry::Registry;
+pub use runner::Runner;
+pub use stage::Stage;
+pub use test_item::{RelayTest, TestItem};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Silence progress output on stderr (e.g. in script mode)
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
@@ -1,224 +1,219 @@
-#![allow(clippy::await_holding_lock)] // we aren't really parallel, doesn't matter.
+use colorful::{Color, Colorful};
+use relay_tester::baseline::{Baseline, Change};
+use relay_tester::config::Config;
+use relay_tester::error::Error;
+use relay_tester::log;
+use relay_tester::registry::Registry;
+use relay_tester::report::Format;
+use relay_tester::runner::Runner;
+use relay_tester::selection::Selection;
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::process::ExitCode;
+
+// Process exit codes
+const EXIT_PASS: u8 = 0;
+const EXIT_FAIL: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+const EXIT_REGRESSION: u8 = 3;
 
-macro_rules! log {
-    ($($arg:tt)*) => {{
-        if ! $crate::globals::GLOBALS.script_mode.load(std::sync::atomic::Ordering::Relaxed) {
-            std::eprintln!($($arg)*);
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(code) => code,
+        Err(e) => {
+            // Not via log!, this must be seen even in script mode
+            eprintln!("{}: {}", "ERROR".color(Color::Red), e);
+            ExitCode::from(EXIT_ERROR)
         }
-    }};
+    }
 }
 
-mod connection;
-mod error;
-mod event_group;
-mod globals;
-mod outcome;
-mod stage;
-mod test_item;
-mod tests;
-
-use crate::error::Error;
-use crate::globals::{Globals, GLOBALS};
-use crate::outcome::Outcome;
-use crate::stage::Stage;
-use crate::test_item::TestItem;
-use colorful::{Color, Colorful};
-use nostr_types::PrivateKey;
-use std::env;
-use std::sync::atomic::Ordering;
-use std::thread;
-use std::time::Duration;
-use strum::IntoEnumIterator;
+async fn run() -> Result<ExitCode, Error> {
+    let args: Vec<String> = env::args().skip(1).collect(); // skip program name
 
-const WAIT: u64 = 2;
+    // Load the config file first, so the command line can override it
+    let mut config = match args.iter().find_map(|a| a.strip_prefix("--config=")) {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
 
-#[tokio::main]
-async fn main() -> Result<(), Error> {
-    // Install crypto provider
-    rustls::crypto::ring::default_provider()
-        .install_default()
-        .expect("Failed to install rustls crypto provider");
-
-    let mut args = env::args();
-    let _ = args.next(); // program name
-
-    let mut relay_url_opt: Option<String> = None;
-    let mut private_key1_opt: Option<String> = None;
-    let mut private_key2_opt: Option<String> = None;
+    let registry = Registry::builtin();
+
+    let mut positional: usize = 0;
+    let mut selection = Selection::default();
+    let mut fail_on_optional: bool = false;
     for a in args {
-        if a.starts_with("--") {
+        if let Some(list) = a.strip_prefix("--only=") {
+            match Selection::parse_list(list, &registry) {
+                Ok(selectors) => selection.only.extend(selectors),
+                Err(bad) => return unknown_selector(&bad),
+            }
+        } else if let Some(list) = a.strip_prefix("--skip=") {
+            match Selection::parse_list(list, &registry) {
+                Ok(selectors) => selection.skip.extend(selectors),
+                Err(bad) => return unknown_selector(&bad),
+            }
+        } else if a.starts_with("--config=") {
+            // already loaded
+        } else if config.apply_override(&a)? {
+            // applied to config
+        } else if a.starts_with("--") {
             match &*a {
-                "--script" => GLOBALS.script_mode.store(true, Ordering::Relaxed),
+                "--script" => config.script = true,
+                "--fail-on-optional" => fail_on_optional = true,
                 _ => return usage(),
             }
-        } else if relay_url_opt.is_none() {
-            relay_url_opt = Some(a);
-        } else if private_key1_opt.is_none() {
-            private_key1_opt = Some(a);
-        } else if private_key2_opt.is_none() {
-            private_key2_opt = Some(a);
         } else {
-            return usage();
+            match positional {
+                0 => config.relay_url = Some(a),
+                1 => config.nsec1 = Some(a),
+                2 => config.nsec2 = Some(a),
+                _ => return usage(),
+            }
+            positional += 1;
         }
     }
 
-    let relay_url = match relay_url_opt {
-        Some(u) => u,
-        None => return usage(),
-    };
-
-    let private_key1 = match private_key1_opt {
-        Some(s) => PrivateKey::try_from_bech32_string(&s)?,
-        None => return usage(),
-    };
+    relay_tester::set_quiet(config.script);
 
-    let private_key2 = match private_key2_opt {
-        Some(s) => PrivateKey::try_from_bech32_string(&s)?,
-        None => return usage(),
+    let mut format_opt: Option<Format> = match config.format {
+        Some(ref f) => match Format::parse(f) {
+            Some(format) => Some(format),
+            None => return usage(),
+        },
+        None => None,
     };
 
-    // post-static init of global variables
-    Globals::init(relay_url, private_key1, private_key2).await?;
+    // Script mode reports as JSON unless told otherwise
+    if format_opt.is_none() && config.script {
+        format_opt = Some(Format::Json);
+    }
 
-    // deadlock detection thread
-    {
-        use parking_lot::deadlock;
+    let output_opt = config.output.clone();
+    let save_baseline_opt = config.save_baseline.clone();
 
-        thread::spawn(move || loop {
-            thread::sleep(Duration::from_secs(10));
-            let deadlocks = deadlock::check_deadlock();
-            if deadlocks.is_empty() {
-                continue;
-            }
+    // Load the baseline now, so a bad path fails before the tests run
+    let baseline_opt = match config.baseline {
+        Some(ref path) => Some(Baseline::load(path)?),
+        None => None,
+    };
 
-            log!("{} deadlocks detected", deadlocks.len());
-            for (i, threads) in deadlocks.iter().enumerate() {
-                log!("Deadlock #{}", i);
-                for t in threads {
-                    log!("Threadn Id {:#?}", t.thread_id());
-                    log!("{:#?}", t.backtrace());
-                }
-            }
-        });
+    if config.relay_url.is_none() || config.nsec1.is_none() || config.nsec2.is_none() {
+        return usage();
     }
 
-    // Run the tests in stages
-    for stage in Stage::iter() {
-        log!("-----------------------------------------------------");
-        log!(
-            "*** Stage: {} ***",
-            format!("{:?}", stage).color(Color::Green3a)
-        );
-        stage.init().await?;
-
-        let mut old_next_sub_id = GLOBALS
-            .connection
-            .read()
-            .as_ref()
-            .unwrap()
-            .next_sub_id
-            .load(Ordering::Relaxed);
-
-        for test_item in TestItem::iter() {
-            if test_item.stage() == stage {
-                log!("\n--* TEST: {} *--------", test_item.name());
-
-                let mut outcome = if stage == Stage::Unknown {
-                    Outcome::err("Test has not been assigned to a stage yet.".to_owned())
-                } else {
-                    test_item.run().await
-                };
-
-                let new_next_sub_id = GLOBALS
-                    .connection
-                    .read()
-                    .as_ref()
-                    .unwrap()
-                    .next_sub_id
-                    .load(Ordering::Relaxed);
-
-                // old=5, new=7:   answer=(5,6)
-                for i in old_next_sub_id..new_next_sub_id {
-                    outcome.subs.push(i);
-                }
-
-                GLOBALS.test_results.write().insert(test_item, outcome);
-
-                old_next_sub_id = new_next_sub_id;
+    let runner = Runner {
+        config,
+        selection,
+        registry,
+    };
+    let run = runner.run().await?;
+    let results = run.results;
+    let summary = run.summary;
+
+    // Machine-readable report
+    if let Some(format) = format_opt {
+        let reporter = format.reporter();
+        match output_opt {
+            Some(path) => {
+                let mut file = File::create(path)?;
+                reporter.report(&results, &mut file)?;
+                file.flush()?;
             }
-
-            thread::sleep(Duration::new(0, 100));
-        }
-    }
-
-    GLOBALS
-        .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .disconnect()
-        .await?;
-
-    // Display the results
-    log!("====================================================");
-    log!("SUMMARY RESULTS\n");
-
-    let mut not_implemented: usize = 0;
-    let mut untested: usize = 0;
-    let mut fail: usize = 0;
-    let mut total: usize = 0;
-
-    for (test_item, outcome) in GLOBALS.test_results.read().iter() {
-        total += 1;
-
-        // Don't print the tests that are not yet implemented
-        if let Some(s) = &outcome.info {
-            if s.contains("NOT YET IMPLEMENTED") {
-                not_implemented += 1;
-                continue;
+            None => {
+                let mut stdout = std::io::stdout().lock();
+                reporter.report(&results, &mut stdout)?;
+                stdout.flush()?;
             }
         }
+    }
 
-        if test_item.required() && matches!(outcome.pass, Some(false)) {
-            fail += 1;
-        }
+    if let Some(path) = save_baseline_opt {
+        Baseline::from_results(&results).save(path)?;
+    }
 
-        if outcome.pass.is_none() {
-            untested += 1;
-        }
+    // With a baseline, only changes matter
+    if let Some(baseline) = baseline_opt {
+        let comparison = baseline.compare(&results);
 
+        log!("====================================================");
+        log!("COMPARED WITH BASELINE\n");
+        log_changes("NEWLY FAILING", &comparison.newly_failing);
+        log_changes("NEWLY PASSING", &comparison.newly_passing);
+        log_changes("CHANGED", &comparison.changed);
         log!(
-            "{}: {}",
-            test_item.name(),
-            outcome.display(test_item.required())
+            "NEWLY_FAILING: {}, NEWLY_PASSING: {}, CHANGED: {}",
+            comparison.newly_failing.len(),
+            comparison.newly_passing.len(),
+            comparison.changed.len()
         );
 
-        if GLOBALS.script_mode.load(Ordering::Relaxed) {
-            let value = serde_json::json!({
-                "test": test_item.name(),
-                "required": test_item.required(),
-                "pass": outcome.pass,
-                "info": outcome.info
-            });
-            println!("{}", value);
-        }
+        return if comparison.regressions(fail_on_optional) > 0 {
+            Ok(ExitCode::from(EXIT_REGRESSION))
+        } else {
+            Ok(ExitCode::from(EXIT_PASS))
+        };
     }
 
-    log!(
-        "FAIL: {}, UNTESTED: {}, NOT_IMPLEMENTED: {}, TOTAL: {}",
-        fail,
-        untested,
-        not_implemented,
-        total
-    );
+    if summary.fail > 0 || (fail_on_optional && summary.optional_fail > 0) {
+        Ok(ExitCode::from(EXIT_FAIL))
+    } else if summary.untested > 0 || summary.prerequisite_failed > 0 {
+        Ok(ExitCode::from(EXIT_ERROR))
+    } else {
+        Ok(ExitCode::from(EXIT_PASS))
+    }
+}
 
-    Ok(())
+fn log_changes(heading: &str, changes: &[Change]) {
+    if changes.is_empty() {
+        return;
+    }
+    log!("{}:", heading);
+    for change in changes {
+        let before = match change.before {
+            Some(ref b) => match b.info {
+                Some(ref info) => format!("{} ({})", b.status, info),
+                None => b.status.clone(),
+            },
+            None => "not in baseline".to_owned(),
+        };
+        let after = match change.after.info {
+            Some(ref info) => format!("{} ({})", change.after.status, info),
+            None => change.after.status.clone(),
+        };
+        log!("  {}: {} -> {}", change.test_item.name(), before, after);
+    }
 }
 
-fn usage() -> Result<(), Error> {
+fn usage() -> Result<ExitCode, Error> {
     log!(
-        "{}: relay-tester <relay_url> <allowed_nsec1> <allowed_nsec2>",
+        "{}: relay-tester [--config=<file>] [--script] [--format=json|junit|tap] [--output=<file>] [--fail-on-optional] [--baseline=<file>] [--save-baseline=<file>] [--transcript-dir=<dir>] [--only=<sel>,...] [--skip=<sel>,...] [<relay_url> <allowed_nsec1> <allowed_nsec2>]",
         "Usage".color(Color::Gold1)
     );
-    Ok(())
+    log!("  The relay url and nsecs may instead come from the config file (or --relay-url=, --nsec1=, --nsec2=)");
+    log!("  Timeouts (milliseconds): --wait-ms= --reconnect-delay-ms= --connect-timeout-ms= --handshake-settle-ms= --auth-timeout-ms= --nip11-timeout-ms= --idle-ms=");
+    log!("  A selector <sel> is a test (e.g. FindById), a module (e.g. delete) or a stage (e.g. registered)");
+    log!("  --fail-on-optional  also exit 1 when optional tests fail (or 3 when they regress)");
+    log!("  --save-baseline=<file>  save the results to compare later runs against");
+    log!("  --baseline=<file>  compare the results against a saved baseline");
+    log!("  --transcript-dir=<dir>  write the frames sent and received by each failing test to <dir>/<TestId>.txt");
+    log!("Exit status:");
+    log!("  {}  all required tests passed", EXIT_PASS);
+    log!("  {}  one or more required tests failed", EXIT_FAIL);
+    log!(
+        "  {}  tests could not be run (tester or connection errors), or bad usage",
+        EXIT_ERROR
+    );
+    log!(
+        "  {}  with --baseline, a required test that passed in the baseline no longer passes",
+        EXIT_REGRESSION
+    );
+    Ok(ExitCode::from(EXIT_ERROR))
+}
+
+fn unknown_selector(selector: &str) -> Result<ExitCode, Error> {
+    log!("Unknown test selector: {}", selector);
+    usage()
 }
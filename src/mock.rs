@@ -0,0 +1,1068 @@
+//! A small in-memory relay, for testing the tester.
+//!
+//! It behaves as a well-mannered relay should, unless told to misbehave
+//! through its [`MockConfig`], so that each test can be checked both to pass
+//! against a good relay and to catch a bad one.
+
+use crate::context::leading_zero_bits;
+use crate::error::Error;
+use futures_util::stream::SplitSink;
+use futures_util::{SinkExt, StreamExt};
+use nostr_types::{Event, EventKind, Filter, Id, RelayMessage, SubscriptionId, Unixtime};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::WebSocketStream;
+use tungstenite::Message;
+
+type Sink = SplitSink<WebSocketStream<TcpStream>, Message>;
+
+/// How the mock relay behaves. The default is a relay that does everything
+/// right.
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    /// Send an AUTH challenge on connect, and check AUTH responses
+    pub auth: bool,
+
+    /// Reject events whose id is not the hash of their contents
+    pub verify_ids: bool,
+
+    /// Reject events with a bad signature
+    pub verify_signatures: bool,
+
+    /// Drop connections that have sent nothing for this long, even those
+    /// with open subscriptions
+    pub idle_timeout: Option<Duration>,
+
+    /// Replace a subscription when a REQ reuses its id (rather than adding
+    /// the new filters to it)
+    pub replace_subscriptions: bool,
+
+    /// End subscriptions on CLOSE
+    pub honour_close: bool,
+
+    /// Ignore all but the first filter of a REQ or COUNT
+    pub only_first_filter: bool,
+
+    /// Store each copy of an event that is sent more than once
+    pub store_duplicates: bool,
+
+    /// Refuse REQs and events until the client has authenticated
+    pub auth_required: bool,
+
+    /// Refuse events until the client has authenticated
+    pub restricted_writes: bool,
+
+    /// Refuse REQs for giftwraps until the client has authenticated
+    pub protect_giftwraps: bool,
+
+    /// Serve giftwraps and DMs only to connections authenticated as their
+    /// author or a recipient
+    pub check_recipients: bool,
+
+    /// Refuse DMs until the client has authenticated
+    pub protect_dm_writes: bool,
+
+    /// The limits advertised in the NIP-11 document
+    pub limits: MockLimits,
+
+    /// Enforce the advertised limits
+    pub enforce_limits: bool,
+
+    /// Answer websocket upgrades with 503 while this many connections are
+    /// open
+    pub max_connections: Option<usize>,
+
+    /// Send EOSE after the stored events
+    pub send_eose: bool,
+
+    /// Keep only the newest replaceable and addressable events
+    pub replace_events: bool,
+
+    /// Act on deletion requests
+    pub honour_deletions: bool,
+
+    /// Answer COUNT requests (NIP-45), rather than closing them
+    pub count: bool,
+
+    /// Honour `search` in filters (NIP-50), rather than closing the request
+    pub search: bool,
+
+    /// Reject expired events and stop serving events once they expire (NIP-40)
+    pub expire_events: bool,
+
+    /// Store ephemeral events (which a relay should not)
+    pub store_ephemeral: bool,
+
+    /// Serve a NIP-11 document
+    pub serve_nip11: bool,
+
+    /// Send the CORS headers NIP-11 asks for with the document
+    pub nip11_cors: bool,
+
+    /// The NIPs claimed in the NIP-11 document
+    pub supported_nips: Vec<u32>,
+
+    /// Fields to add to (or replace in) the NIP-11 document
+    pub nip11_extra: Map<String, Value>,
+}
+
+impl Default for MockConfig {
+    fn default() -> MockConfig {
+        MockConfig {
+            auth: true,
+            verify_ids: true,
+            verify_signatures: true,
+            idle_timeout: None,
+            replace_subscriptions: true,
+            honour_close: true,
+            only_first_filter: false,
+            store_duplicates: false,
+            auth_required: false,
+            restricted_writes: false,
+            protect_giftwraps: true,
+            check_recipients: true,
+            protect_dm_writes: true,
+            limits: MockLimits::default(),
+            enforce_limits: true,
+            max_connections: None,
+            send_eose: true,
+            replace_events: true,
+            honour_deletions: true,
+            count: true,
+            search: true,
+            expire_events: true,
+            store_ephemeral: false,
+            serve_nip11: true,
+            nip11_cors: true,
+            supported_nips: vec![1, 9, 11, 40, 42, 45, 50],
+            nip11_extra: Map::new(),
+        }
+    }
+}
+
+/// The NIP-11 `limitation` values of the mock relay. Those that are `None`
+/// are neither advertised nor enforced.
+#[derive(Debug, Clone)]
+pub struct MockLimits {
+    pub max_message_length: Option<usize>,
+    pub max_subscriptions: Option<usize>,
+    pub max_filters: Option<usize>,
+    pub max_limit: Option<usize>,
+    pub max_subid_length: Option<usize>,
+    pub max_event_tags: Option<usize>,
+    pub max_content_length: Option<usize>,
+    pub min_pow_difficulty: Option<u32>,
+    pub created_at_lower_limit: Option<i64>,
+    pub created_at_upper_limit: Option<i64>,
+}
+
+impl Default for MockLimits {
+    fn default() -> MockLimits {
+        MockLimits {
+            max_message_length: Some(131072),
+            max_subscriptions: Some(20),
+            max_filters: Some(10),
+            max_limit: Some(500),
+            max_subid_length: Some(64),
+            max_event_tags: Some(100),
+            max_content_length: Some(8192),
+            min_pow_difficulty: None,
+            created_at_lower_limit: None,
+            created_at_upper_limit: None,
+        }
+    }
+}
+
+/// A running mock relay, listening on a local port until dropped
+pub struct MockRelay {
+    addr: SocketAddr,
+    listener_task: JoinHandle<()>,
+}
+
+impl MockRelay {
+    pub async fn start(config: MockConfig) -> Result<MockRelay, Error> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        let (live, _) = broadcast::channel(1024);
+        let shared = Arc::new(Shared {
+            config,
+            url: format!("ws://{}", addr),
+            store: Mutex::new(Store::default()),
+            live,
+            connections: AtomicUsize::new(0),
+        });
+
+        let listener_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(shared.clone(), stream));
+            }
+        });
+
+        Ok(MockRelay {
+            addr,
+            listener_task,
+        })
+    }
+
+    /// The websocket url to connect to, e.g. "ws://127.0.0.1:40123"
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.addr)
+    }
+}
+
+impl Drop for MockRelay {
+    fn drop(&mut self) {
+        self.listener_task.abort();
+    }
+}
+
+struct Shared {
+    config: MockConfig,
+    url: String,
+    store: Mutex<Store>,
+
+    // Every event accepted, for delivery to open subscriptions
+    live: broadcast::Sender<Event>,
+
+    // Websocket connections open now
+    connections: AtomicUsize,
+}
+
+#[derive(Default)]
+struct Store {
+    events: Vec<Stored>,
+
+    // Deleted event ids, and who deleted them (both in hex)
+    deleted_ids: HashMap<String, String>,
+
+    // Deleted addresses (kind, pubkey, d-tag), and the time of the deletion
+    deleted_addrs: HashMap<(u32, String, String), Unixtime>,
+}
+
+struct Stored {
+    event: Event,
+    kind: u32,
+    pubkey: String,
+    d: String,
+    expiration: Option<i64>,
+}
+
+// A message to the client
+enum Reply {
+    Relay(RelayMessage),
+
+    // NIP-45, which nostr-types has no message for
+    Count(String, usize),
+}
+
+impl From<RelayMessage> for Reply {
+    fn from(message: RelayMessage) -> Reply {
+        Reply::Relay(message)
+    }
+}
+
+// Per-connection state
+struct Session {
+    challenge: String,
+    authenticated: bool,
+    subs: HashMap<String, Vec<Query>>,
+
+    // Who the client has authenticated as (in hex)
+    pubkeys: Vec<String>,
+}
+
+// A filter, and the NIP-50 search nostr-types doesn't parse
+struct Query {
+    filter: Filter,
+    search: Option<String>,
+}
+
+async fn serve(shared: Arc<Shared>, mut stream: TcpStream) {
+    // Peek at the request to tell websocket upgrades from NIP-11 fetches
+    let mut buf = [0u8; 8192];
+    let head = loop {
+        let n = match stream.peek(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+        let head = String::from_utf8_lossy(&buf[..n]).to_ascii_lowercase();
+        if head.contains("\r\n\r\n") || n == buf.len() {
+            break head;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    };
+
+    if head.contains("upgrade: websocket") {
+        let open = shared.connections.fetch_add(1, Ordering::SeqCst);
+        if shared.config.max_connections.map(|m| open >= m) == Some(true) {
+            let _ = stream.read(&mut buf).await;
+            let response = "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        } else if let Ok(websocket) = tokio_tungstenite::accept_async(stream).await {
+            serve_websocket(shared.clone(), websocket).await;
+        }
+        shared.connections.fetch_sub(1, Ordering::SeqCst);
+    } else {
+        let _ = stream.read(&mut buf).await;
+        let response = if shared.config.serve_nip11 && head.contains("application/nostr+json") {
+            let body = nip11(&shared.config).to_string();
+            let cors = if shared.config.nip11_cors {
+                "Access-Control-Allow-Origin: *\r\nAccess-Control-Allow-Headers: *\r\nAccess-Control-Allow-Methods: GET, OPTIONS\r\n"
+            } else {
+                ""
+            };
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/nostr+json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                cors,
+                body.len(),
+                body
+            )
+        } else {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned()
+        };
+        let _ = stream.write_all(response.as_bytes()).await;
+        let _ = stream.shutdown().await;
+    }
+}
+
+fn limitation(config: &MockConfig) -> Value {
+    let limits = &config.limits;
+    let mut limitation = serde_json::json!({
+        "auth_required": config.auth_required,
+        "payment_required": false,
+        "restricted_writes": config.restricted_writes,
+    });
+    let numbers = [
+        (
+            "max_message_length",
+            limits.max_message_length.map(|n| n as i64),
+        ),
+        (
+            "max_subscriptions",
+            limits.max_subscriptions.map(|n| n as i64),
+        ),
+        ("max_filters", limits.max_filters.map(|n| n as i64)),
+        ("max_limit", limits.max_limit.map(|n| n as i64)),
+        (
+            "max_subid_length",
+            limits.max_subid_length.map(|n| n as i64),
+        ),
+        ("max_event_tags", limits.max_event_tags.map(|n| n as i64)),
+        (
+            "max_content_length",
+            limits.max_content_length.map(|n| n as i64),
+        ),
+        (
+            "min_pow_difficulty",
+            limits.min_pow_difficulty.map(|n| n as i64),
+        ),
+        ("created_at_lower_limit", limits.created_at_lower_limit),
+        ("created_at_upper_limit", limits.created_at_upper_limit),
+    ];
+    if let Value::Object(ref mut map) = limitation {
+        for (key, value) in numbers {
+            if let Some(value) = value {
+                map.insert(key.to_owned(), value.into());
+            }
+        }
+    }
+    limitation
+}
+
+fn nip11(config: &MockConfig) -> Value {
+    let mut document = serde_json::json!({
+        "name": "relay-tester mock relay",
+        "description": "An in-memory relay for testing relay-tester",
+        "pubkey": "0000000000000000000000000000000000000000000000000000000000000001",
+        "software": "relay-tester",
+        "version": env!("CARGO_PKG_VERSION"),
+        "supported_nips": config.supported_nips,
+        "limitation": limitation(config),
+        // Ephemeral events are not kept, everything else is kept forever
+        "retention": [
+            { "kinds": [[20000, 29999]], "time": 0 },
+        ],
+    });
+    if let Value::Object(ref mut map) = document {
+        for (key, value) in config.nip11_extra.iter() {
+            map.insert(key.clone(), value.clone());
+        }
+    }
+    document
+}
+
+async fn serve_websocket(shared: Arc<Shared>, websocket: WebSocketStream<TcpStream>) {
+    let (mut sink, mut stream) = websocket.split();
+    let mut live = shared.live.subscribe();
+    let mut session = Session {
+        challenge: format!("{:016x}", rand::random::<u64>()),
+        authenticated: false,
+        subs: HashMap::new(),
+        pubkeys: Vec::new(),
+    };
+
+    if shared.config.auth {
+        let auth = RelayMessage::Auth(session.challenge.clone()).into();
+        if send(&mut sink, &auth).await.is_err() {
+            return;
+        }
+    }
+
+    // When the client was last heard from
+    let mut heard = tokio::time::Instant::now();
+
+    loop {
+        let deadline = shared.config.idle_timeout.map(|timeout| heard + timeout);
+        let idle = async move {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let replies = tokio::select! {
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(s))) => {
+                    heard = tokio::time::Instant::now();
+                    session.handle(&shared, &s)
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+            event = live.recv() => match event {
+                Ok(event) => session.deliver(&shared, &event),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            _ = idle => {
+                let _ = sink.send(Message::Close(None)).await;
+                return;
+            }
+        };
+
+        for reply in replies.iter() {
+            if send(&mut sink, reply).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+async fn send(sink: &mut Sink, reply: &Reply) -> Result<(), Error> {
+    let wire = match reply {
+        Reply::Relay(message) => serde_json::to_string(message)?,
+        Reply::Count(sub, count) => {
+            serde_json::json!(["COUNT", sub, { "count": count }]).to_string()
+        }
+    };
+    sink.send(Message::Text(wire)).await?;
+    Ok(())
+}
+
+fn closed(sub: String, message: &str) -> Vec<Reply> {
+    vec![RelayMessage::Closed(SubscriptionId(sub), message.to_owned()).into()]
+}
+
+impl Session {
+    fn handle(&mut self, shared: &Shared, text: &str) -> Vec<Reply> {
+        let notice = |s: &str| vec![RelayMessage::Notice(s.to_owned()).into()];
+
+        let array = match serde_json::from_str::<Value>(text) {
+            Ok(Value::Array(array)) => array,
+            _ => return notice("invalid: could not parse message"),
+        };
+
+        match array.first().and_then(|v| v.as_str()) {
+            Some("EVENT") if array.len() == 2 => {
+                let (id, ok, reason) = match self.refuse_event(shared, text, &array[1]) {
+                    Some(reason) => (event_id(&array[1]), false, reason),
+                    None => shared.accept_event(&array[1]),
+                };
+                match id {
+                    Some(id) => vec![RelayMessage::Ok(id, ok, reason).into()],
+                    None => notice(&reason),
+                }
+            }
+            Some(verb @ ("REQ" | "COUNT")) if array.len() >= 3 => {
+                let sub = match array[1].as_str() {
+                    Some(sub) => sub.to_owned(),
+                    None => return notice("invalid: subscription id is not a string"),
+                };
+                if let Some(reason) = self.refuse_req(shared, text, &sub, array.len() - 2) {
+                    return closed(sub, &reason);
+                }
+                let mut filters: Vec<Query> = Vec::new();
+                for value in &array[2..] {
+                    match Query::parse(value) {
+                        Ok(query) => filters.push(query),
+                        Err(e) => return closed(sub, &format!("invalid: {}", e)),
+                    }
+                }
+                if shared.config.only_first_filter {
+                    filters.truncate(1);
+                }
+                if shared.config.enforce_limits {
+                    if let Some(max) = shared.config.limits.max_limit {
+                        for query in filters.iter_mut() {
+                            query.filter.limit = query.filter.limit.map(|l| l.min(max));
+                        }
+                    }
+                }
+                if shared.config.protect_giftwraps
+                    && !self.authenticated
+                    && filters
+                        .iter()
+                        .any(|q| q.filter.kinds.contains(&EventKind::GiftWrap))
+                {
+                    return closed(sub, "auth-required: giftwraps are only served after AUTH");
+                }
+                if !shared.config.search && filters.iter().any(|q| q.search.is_some()) {
+                    return closed(sub, "unsupported: search is not supported");
+                }
+                if verb == "REQ" {
+                    self.req(shared, sub, filters)
+                } else if shared.config.count {
+                    vec![Reply::Count(sub, shared.count(&filters))]
+                } else {
+                    closed(sub, "error: COUNT is not supported")
+                }
+            }
+            Some("CLOSE") if array.len() == 2 => {
+                if let Some(sub) = array[1].as_str().filter(|_| shared.config.honour_close) {
+                    self.subs.remove(sub);
+                }
+                vec![]
+            }
+            Some("AUTH") if array.len() == 2 => {
+                let (id, ok, reason) = self.auth(shared, &array[1]);
+                self.authenticated |= ok;
+                if let Some(pubkey) = array[1].get("pubkey").and_then(|p| p.as_str()) {
+                    if ok && !self.pubkeys.iter().any(|p| p == pubkey) {
+                        self.pubkeys.push(pubkey.to_owned());
+                    }
+                }
+                match id {
+                    Some(id) => vec![RelayMessage::Ok(id, ok, reason).into()],
+                    None => notice(&reason),
+                }
+            }
+            _ => notice("invalid: unknown message"),
+        }
+    }
+
+    // Why an event may not be accepted from this client, if it may not
+    fn refuse_event(&self, shared: &Shared, text: &str, value: &Value) -> Option<String> {
+        let config = &shared.config;
+        if config.auth_required && !self.authenticated {
+            return Some("auth-required: please AUTH first".to_owned());
+        }
+        if config.restricted_writes && !self.authenticated {
+            return Some("restricted: only authenticated users may write".to_owned());
+        }
+        if config.protect_dm_writes
+            && !self.authenticated
+            && value.get("kind").and_then(|k| k.as_u64()) == Some(4)
+        {
+            return Some("auth-required: DMs are only accepted after AUTH".to_owned());
+        }
+        if !config.enforce_limits {
+            return None;
+        }
+
+        let limits = &config.limits;
+        let now = Unixtime::now().0;
+        let tags = value
+            .get("tags")
+            .and_then(|t| t.as_array())
+            .map(|t| t.len());
+        let content = value.get("content").and_then(|c| c.as_str());
+        let created_at = value.get("created_at").and_then(|c| c.as_i64());
+        let difficulty = event_id(value).map(|id| leading_zero_bits(&id.0));
+
+        if limits.max_message_length.map(|m| text.len() > m) == Some(true) {
+            Some("invalid: message is too long".to_owned())
+        } else if limits.max_event_tags.zip(tags).map(|(m, t)| t > m) == Some(true) {
+            Some("invalid: too many tags".to_owned())
+        } else if limits
+            .max_content_length
+            .zip(content)
+            .map(|(m, c)| c.chars().count() > m)
+            == Some(true)
+        {
+            Some("invalid: content is too long".to_owned())
+        } else if let Some((min, bits)) = limits.min_pow_difficulty.zip(difficulty) {
+            (bits < min).then(|| format!("pow: difficulty {} is less than {}", bits, min))
+        } else if limits
+            .created_at_lower_limit
+            .zip(created_at)
+            .map(|(l, c)| c < now - l)
+            == Some(true)
+        {
+            Some("invalid: created_at is too far in the past".to_owned())
+        } else if limits
+            .created_at_upper_limit
+            .zip(created_at)
+            .map(|(l, c)| c > now + l)
+            == Some(true)
+        {
+            Some("invalid: created_at is too far in the future".to_owned())
+        } else {
+            None
+        }
+    }
+
+    // Why a REQ (or COUNT) may not be served to this client, if it may not
+    fn refuse_req(&self, shared: &Shared, text: &str, sub: &str, filters: usize) -> Option<String> {
+        let config = &shared.config;
+        if config.auth_required && !self.authenticated {
+            return Some("auth-required: please AUTH first".to_owned());
+        }
+        if !config.enforce_limits {
+            return None;
+        }
+
+        let limits = &config.limits;
+        let is_new = !self.subs.contains_key(sub);
+
+        if limits.max_message_length.map(|m| text.len() > m) == Some(true) {
+            Some("invalid: message is too long".to_owned())
+        } else if limits.max_subid_length.map(|m| sub.len() > m) == Some(true) {
+            Some("invalid: subscription id is too long".to_owned())
+        } else if limits.max_filters.map(|m| filters > m) == Some(true) {
+            Some("invalid: too many filters".to_owned())
+        } else if is_new && limits.max_subscriptions.map(|m| self.subs.len() >= m) == Some(true) {
+            Some("rate-limited: too many subscriptions".to_owned())
+        } else {
+            None
+        }
+    }
+
+    fn req(&mut self, shared: &Shared, sub: String, filters: Vec<Query>) -> Vec<Reply> {
+        let mut replies: Vec<Reply> = shared
+            .query(&filters)
+            .into_iter()
+            .filter(|event| self.may_read(shared, event))
+            .map(|event| RelayMessage::Event(SubscriptionId(sub.clone()), Box::new(event)).into())
+            .collect();
+
+        if !shared.config.send_eose {
+            self.subscribe(shared, sub, filters);
+            return replies;
+        }
+
+        replies.push(RelayMessage::Eose(SubscriptionId(sub.clone())).into());
+
+        // Once everything asked for by id has been sent, nothing more can match
+        if filters.iter().all(|q| !q.filter.ids.is_empty()) {
+            self.subs.remove(&sub);
+            replies.extend(closed(sub, ""));
+        } else {
+            self.subscribe(shared, sub, filters);
+        }
+
+        replies
+    }
+
+    fn subscribe(&mut self, shared: &Shared, sub: String, filters: Vec<Query>) {
+        if shared.config.replace_subscriptions {
+            self.subs.insert(sub, filters);
+        } else {
+            self.subs.entry(sub).or_default().extend(filters);
+        }
+    }
+
+    fn deliver(&self, shared: &Shared, event: &Event) -> Vec<Reply> {
+        if !self.may_read(shared, event) {
+            return vec![];
+        }
+        self.subs
+            .iter()
+            .filter(|(_, filters)| filters.iter().any(|q| q.matches(event)))
+            .map(|(sub, _)| {
+                RelayMessage::Event(SubscriptionId(sub.clone()), Box::new(event.clone())).into()
+            })
+            .collect()
+    }
+
+    // Whether the event may be served to this client
+    fn may_read(&self, shared: &Shared, event: &Event) -> bool {
+        let private = matches!(
+            event.kind,
+            EventKind::GiftWrap | EventKind::EncryptedDirectMessage
+        );
+        if !shared.config.check_recipients || !private {
+            return true;
+        }
+        let author = event.pubkey.as_hex_string();
+        let recipients = match serde_json::to_value(event) {
+            Ok(value) => tag_values(&value, "p"),
+            Err(_) => vec![],
+        };
+        self.pubkeys
+            .iter()
+            .any(|p| *p == author || recipients.contains(p))
+    }
+
+    fn auth(&mut self, shared: &Shared, value: &Value) -> (Option<Id>, bool, String) {
+        let (id, event) = match shared.check_event(value) {
+            Ok(ok) => ok,
+            Err((id, reason)) => return (id, false, reason),
+        };
+
+        let reason = if u32::from(event.kind) != 22242 {
+            Some("invalid: AUTH event must be kind 22242")
+        } else if tag_values(value, "relay")
+            .iter()
+            .all(|r| r.trim_end_matches('/') != shared.url)
+        {
+            Some("invalid: AUTH event is for a different relay")
+        } else if !tag_values(value, "challenge").contains(&self.challenge) {
+            Some("invalid: AUTH event has the wrong challenge")
+        } else if Unixtime::now().0.abs_diff(event.created_at.0) > 600 {
+            Some("invalid: AUTH event is too old or too new")
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => (Some(id), false, reason.to_owned()),
+            None => (Some(id), true, "".to_owned()),
+        }
+    }
+}
+
+impl Shared {
+    /// Check and store an event, returning the OK response
+    fn accept_event(&self, value: &Value) -> (Option<Id>, bool, String) {
+        let (id, event) = match self.check_event(value) {
+            Ok(ok) => ok,
+            Err((id, reason)) => return (id, false, reason),
+        };
+
+        let kind = u32::from(event.kind);
+        let pubkey = event.pubkey.as_hex_string();
+        let d = tag_values(value, "d")
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        let expiration = tag_values(value, "expiration")
+            .into_iter()
+            .next()
+            .and_then(|s| s.parse::<i64>().ok())
+            .filter(|_| self.config.expire_events);
+
+        if expiration.map(|t| t <= Unixtime::now().0).unwrap_or(false) {
+            return (Some(id), false, "invalid: event has expired".to_owned());
+        }
+
+        {
+            let mut store = self.store.lock().unwrap();
+
+            if !self.config.store_duplicates && store.events.iter().any(|s| s.event.id == id) {
+                return (
+                    Some(id),
+                    true,
+                    "duplicate: already have this event".to_owned(),
+                );
+            }
+
+            if self.config.honour_deletions {
+                if store.deleted_ids.get(&id.as_hex_string()) == Some(&pubkey) {
+                    return (
+                        Some(id),
+                        false,
+                        "blocked: this event was deleted".to_owned(),
+                    );
+                }
+                if let Some(when) = store.deleted_addrs.get(&(kind, pubkey.clone(), d.clone())) {
+                    if is_addressable(kind) && event.created_at <= *when {
+                        return (
+                            Some(id),
+                            false,
+                            "blocked: this address was deleted".to_owned(),
+                        );
+                    }
+                }
+            }
+
+            let is_ephemeral = (20000..30000).contains(&kind);
+
+            if self.config.replace_events && (is_replaceable(kind) || is_addressable(kind)) {
+                let same = |s: &Stored| {
+                    s.kind == kind && s.pubkey == pubkey && (!is_addressable(kind) || s.d == d)
+                };
+                let newer_exists = store.events.iter().filter(|s| same(s)).any(|s| {
+                    s.event.created_at > event.created_at
+                        || (s.event.created_at == event.created_at && s.event.id.0 < id.0)
+                });
+                if newer_exists {
+                    return (
+                        Some(id),
+                        false,
+                        "duplicate: a newer version of this event exists".to_owned(),
+                    );
+                }
+                store.events.retain(|s| !same(s));
+            }
+
+            if kind == 5 && self.config.honour_deletions {
+                store.delete(&event, value);
+            }
+
+            if !is_ephemeral || self.config.store_ephemeral {
+                store.events.push(Stored {
+                    event: event.clone(),
+                    kind,
+                    pubkey,
+                    d,
+                    expiration,
+                });
+            }
+        }
+
+        let _ = self.live.send(event);
+
+        (Some(id), true, "".to_owned())
+    }
+
+    /// Parse an event and verify its id and signature (as configured).
+    /// On failure, the id (if there is one) and the reason.
+    fn check_event(&self, value: &Value) -> Result<(Id, Event), (Option<Id>, String)> {
+        let id = match event_id(value) {
+            Some(id) => id,
+            None => return Err((None, "invalid: event has no id".to_owned())),
+        };
+
+        if self.config.verify_ids && computed_id(value) != Some(id) {
+            return Err((Some(id), "invalid: bad event id".to_owned()));
+        }
+
+        if self.config.verify_signatures && !signature_is_valid(value, id) {
+            return Err((Some(id), "invalid: bad signature".to_owned()));
+        }
+
+        match serde_json::from_value::<Event>(value.clone()) {
+            Ok(event) => Ok((id, event)),
+            Err(e) => Err((Some(id), format!("invalid: {}", e))),
+        }
+    }
+
+    /// How many stored events match any of the filters
+    fn count(&self, filters: &[Query]) -> usize {
+        let store = self.store.lock().unwrap();
+        store
+            .events
+            .iter()
+            .filter(|s| s.is_live() && filters.iter().any(|q| q.matches(&s.event)))
+            .count()
+    }
+
+    /// Stored events matching any of the filters, newest first
+    fn query(&self, filters: &[Query]) -> Vec<Event> {
+        let store = self.store.lock().unwrap();
+
+        let mut output: Vec<Event> = Vec::new();
+        for query in filters {
+            let mut found: Vec<&Event> = store
+                .events
+                .iter()
+                .filter(|s| s.is_live())
+                .map(|s| &s.event)
+                .filter(|e| query.matches(e))
+                .collect();
+            sort_newest_first(&mut found);
+            if let Some(limit) = query.filter.limit {
+                found.truncate(limit);
+            }
+            for event in found {
+                if !output.iter().any(|e| e.id == event.id) {
+                    output.push(event.clone());
+                }
+            }
+        }
+
+        let mut refs: Vec<&Event> = output.iter().collect();
+        sort_newest_first(&mut refs);
+        refs.into_iter().cloned().collect()
+    }
+}
+
+impl Store {
+    // Apply a deletion request to the events of its author
+    fn delete(&mut self, deletion: &Event, value: &Value) {
+        let author = deletion.pubkey.as_hex_string();
+
+        for e in tag_values(value, "e") {
+            if let Ok(id) = Id::try_from_hex_string(&e) {
+                self.events
+                    .retain(|s| s.event.id != id || s.pubkey != author);
+                self.deleted_ids.insert(e, author.clone());
+            }
+        }
+
+        for a in tag_values(value, "a") {
+            let mut parts = a.splitn(3, ':');
+            let (kind, pubkey, d) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(k), Some(p), Some(d)) => match k.parse::<u32>() {
+                    Ok(k) => (k, p.to_owned(), d.to_owned()),
+                    Err(_) => continue,
+                },
+                _ => continue,
+            };
+            if pubkey != author {
+                continue;
+            }
+            self.events.retain(|s| {
+                !(s.kind == kind
+                    && s.pubkey == pubkey
+                    && s.d == d
+                    && s.event.created_at <= deletion.created_at)
+            });
+            self.deleted_addrs
+                .insert((kind, pubkey, d), deletion.created_at);
+        }
+    }
+}
+
+impl Stored {
+    // Whether the event is still to be served
+    fn is_live(&self) -> bool {
+        self.expiration
+            .map(|t| t > Unixtime::now().0)
+            .unwrap_or(true)
+    }
+}
+
+impl Query {
+    fn parse(value: &Value) -> Result<Query, serde_json::Error> {
+        let mut value = value.clone();
+        let search = match value {
+            Value::Object(ref mut map) => map.remove("search"),
+            _ => None,
+        };
+        Ok(Query {
+            filter: serde_json::from_value(value)?,
+            search: search.and_then(|s| s.as_str().map(|s| s.to_owned())),
+        })
+    }
+
+    fn matches(&self, event: &Event) -> bool {
+        let filter = &self.filter;
+        filter.event_matches(event)
+            && filter.since.map(|t| event.created_at >= t).unwrap_or(true)
+            && filter.until.map(|t| event.created_at <= t).unwrap_or(true)
+            && self
+                .search
+                .as_ref()
+                .map(|s| found(s, event))
+                .unwrap_or(true)
+    }
+}
+
+// Whether the content holds every search term. Extensions (`key:value`)
+// aren't supported, so are ignored.
+fn found(search: &str, event: &Event) -> bool {
+    let content = event.content.to_lowercase();
+    search
+        .split_whitespace()
+        .filter(|term| !term.contains(':'))
+        .all(|term| content.contains(&term.to_lowercase()))
+}
+
+fn sort_newest_first(events: &mut [&Event]) {
+    events.sort_by(|a, b| {
+        b.created_at
+            .0
+            .cmp(&a.created_at.0)
+            .then(a.id.0.cmp(&b.id.0))
+    });
+}
+
+fn event_id(value: &Value) -> Option<Id> {
+    value
+        .get("id")
+        .and_then(|v| v.as_str())
+        .and_then(|s| Id::try_from_hex_string(s).ok())
+}
+
+fn is_replaceable(kind: u32) -> bool {
+    kind == 0 || kind == 3 || (10000..20000).contains(&kind)
+}
+
+fn is_addressable(kind: u32) -> bool {
+    (30000..40000).contains(&kind)
+}
+
+/// The second element of each tag with the given name
+fn tag_values(value: &Value, name: &str) -> Vec<String> {
+    let tags = match value.get("tags").and_then(|t| t.as_array()) {
+        Some(tags) => tags,
+        None => return vec![],
+    };
+    tags.iter()
+        .filter_map(|t| t.as_array())
+        .filter(|t| t.first().and_then(|n| n.as_str()) == Some(name))
+        .filter_map(|t| t.get(1).and_then(|v| v.as_str()).map(|s| s.to_owned()))
+        .collect()
+}
+
+// The id an event should have: the hash of its NIP-01 serialization
+fn computed_id(value: &Value) -> Option<Id> {
+    use secp256k1::hashes::Hash;
+
+    let serial = Value::Array(vec![
+        0.into(),
+        value.get("pubkey")?.clone(),
+        value.get("created_at")?.clone(),
+        value.get("kind")?.clone(),
+        value.get("tags")?.clone(),
+        value.get("content")?.clone(),
+    ]);
+    let serial = serde_json::to_string(&serial).ok()?;
+    let hash = secp256k1::hashes::sha256::Hash::hash(serial.as_bytes());
+    Some(Id(hash.to_byte_array()))
+}
+
+fn signature_is_valid(value: &Value, id: Id) -> bool {
+    use secp256k1::{schnorr, Message, XOnlyPublicKey, SECP256K1};
+
+    let field = |name: &str| {
+        value
+            .get(name)
+            .and_then(|v| v.as_str())
+            .and_then(hex_decode)
+    };
+    let (pubkey, sig) = match (field("pubkey"), field("sig")) {
+        (Some(pubkey), Some(sig)) => (pubkey, sig),
+        _ => return false,
+    };
+    let (pubkey, sig) = match (
+        XOnlyPublicKey::from_slice(&pubkey),
+        schnorr::Signature::from_slice(&sig),
+    ) {
+        (Ok(pubkey), Ok(sig)) => (pubkey, sig),
+        _ => return false,
+    };
+    let message = Message::from_digest(id.0);
+    SECP256K1.verify_schnorr(&sig, &message, &pubkey).is_ok()
+}
+
+fn hex_decode(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
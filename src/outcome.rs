@@ -1,35 +1,115 @@
+use crate::transcript::Transcript;
 use colorful::{Color, Colorful};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// The relay behaved as tested for
+    Pass,
+
+    /// The relay misbehaved (or, for optional tests, lacks the feature)
+    Fail,
+
+    /// The test was deliberately not run, for the given reason
+    Skipped(String),
+
+    /// The test has not been written yet
+    NotImplemented,
+
+    /// Something went wrong in the tester, so we don't know
+    TesterError,
+
+    /// Setup the test depends upon (e.g. submitting event group A) failed
+    PrerequisiteFailed,
+}
+
+impl Default for Status {
+    fn default() -> Status {
+        Status::Skipped("Not run".to_owned())
+    }
+}
+
+impl Status {
+    /// A stable machine-readable name
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Status::Pass => "pass",
+            Status::Fail => "fail",
+            Status::Skipped(_) => "skipped",
+            Status::NotImplemented => "not_implemented",
+            Status::TesterError => "tester_error",
+            Status::PrerequisiteFailed => "prerequisite_failed",
+        }
+    }
+
+    /// The old tri-state result: Some(true) pass, Some(false) fail, None if not tested
+    pub fn passed(&self) -> Option<bool> {
+        match *self {
+            Status::Pass => Some(true),
+            Status::Fail => Some(false),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, Default, Clone)]
 pub struct Outcome {
-    pub pass: Option<bool>,
+    pub status: Status,
     pub info: Option<String>,
     pub subs: Vec<usize>,
+    pub duration: Duration,
+
+    /// The frames exchanged with the relay while the test ran
+    pub transcript: Transcript,
 }
 
 impl Outcome {
-    pub fn pass(info: Option<String>) -> Outcome {
+    fn new(status: Status, info: Option<String>) -> Outcome {
         Outcome {
-            pass: Some(true),
+            status,
             info,
             subs: Vec::new(),
+            duration: Duration::ZERO,
+            transcript: Transcript::default(),
         }
     }
 
+    pub fn pass(info: Option<String>) -> Outcome {
+        Outcome::new(Status::Pass, info)
+    }
+
     pub fn fail(info: Option<String>) -> Outcome {
-        Outcome {
-            pass: Some(false),
-            info,
-            subs: Vec::new(),
-        }
+        Outcome::new(Status::Fail, info)
     }
 
     pub fn err(info: String) -> Outcome {
-        Outcome {
-            pass: None,
-            info: Some(info),
-            subs: Vec::new(),
-        }
+        Outcome::new(Status::TesterError, Some(info))
+    }
+
+    pub fn skipped(reason: String) -> Outcome {
+        Outcome::new(Status::Skipped(reason), None)
+    }
+
+    pub fn not_implemented() -> Outcome {
+        Outcome::new(Status::NotImplemented, None)
+    }
+
+    pub fn prerequisite_failed(info: String) -> Outcome {
+        Outcome::new(Status::PrerequisiteFailed, Some(info))
+    }
+
+    /// Whether the relay misbehaved, or we could not tell because of an
+    /// error. These are the outcomes worth a closer look.
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self.status,
+            Status::Fail | Status::TesterError | Status::PrerequisiteFailed
+        )
+    }
+
+    /// The subscription ids used by this test, e.g. ["sub3", "sub4"]
+    pub fn sub_names(&self) -> Vec<String> {
+        self.subs.iter().map(|i| format!("sub{}", i)).collect()
     }
 
     fn subs_str(&self) -> String {
@@ -53,46 +133,35 @@ impl Outcome {
 
 impl Outcome {
     pub fn display(&self, required: bool) -> String {
-        match required {
-            false => match self.pass {
-                None => match self.info {
-                    None => format!("{}", "UNTESTED".color(Color::Grey50)),
-                    Some(ref s) => format!("{} ({})", "UNTESTED".color(Color::Grey50), s),
-                },
-                Some(false) => match self.info {
-                    None => format!("{}{}", self.subs_str(), "NO".color(Color::DarkGoldenrod)),
-                    Some(ref s) => format!(
-                        "{}{} ({})",
-                        self.subs_str(),
-                        "NO".color(Color::DarkGoldenrod),
-                        s
-                    ),
-                },
-                Some(true) => match self.info {
-                    None => format!("{}{} ", self.subs_str(), "YES".color(Color::Green)),
-                    Some(ref s) => {
-                        format!("{}{} ({})", self.subs_str(), "YES".color(Color::Green), s)
-                    }
-                },
-            },
-            true => match self.pass {
-                None => match self.info {
-                    None => format!("{}", "UNTESTED".color(Color::Grey50)),
-                    Some(ref s) => format!("{} ({})", "UNTESTED".color(Color::Grey50), s),
-                },
-                Some(false) => match self.info {
-                    None => format!("{}{}", self.subs_str(), "FAIL".color(Color::Red3a)),
-                    Some(ref s) => {
-                        format!("{}{} ({})", self.subs_str(), "FAIL".color(Color::Red3a), s)
-                    }
-                },
-                Some(true) => match self.info {
-                    None => format!("{}{} ", self.subs_str(), "PASS".color(Color::Green)),
-                    Some(ref s) => {
-                        format!("{}{} ({})", self.subs_str(), "PASS".color(Color::Green), s)
-                    }
-                },
-            },
+        let (subs, label) = match (&self.status, required) {
+            (Status::Pass, false) => (self.subs_str(), format!("{}", "YES".color(Color::Green))),
+            (Status::Pass, true) => (self.subs_str(), format!("{}", "PASS".color(Color::Green))),
+            (Status::Fail, false) => (
+                self.subs_str(),
+                format!("{}", "NO".color(Color::DarkGoldenrod)),
+            ),
+            (Status::Fail, true) => (self.subs_str(), format!("{}", "FAIL".color(Color::Red3a))),
+            (Status::Skipped(reason), _) => (
+                String::new(),
+                format!("{} ({})", "SKIPPED".color(Color::Grey50), reason),
+            ),
+            (Status::NotImplemented, _) => (
+                String::new(),
+                format!("{}", "NOT IMPLEMENTED".color(Color::Grey50)),
+            ),
+            (Status::TesterError, _) => (
+                String::new(),
+                format!("{}", "UNTESTED".color(Color::Grey50)),
+            ),
+            (Status::PrerequisiteFailed, _) => (
+                String::new(),
+                format!("{}", "PREREQUISITE FAILED".color(Color::Orange1)),
+            ),
+        };
+
+        match self.info {
+            None => format!("{}{}", subs, label),
+            Some(ref s) => format!("{}{} ({})", subs, label, s),
         }
     }
 }
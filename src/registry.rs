@@ -0,0 +1,53 @@
+use crate::error::Error;
+use crate::test_item::{RelayTest, TestItem};
+use std::fmt;
+
+/// The tests available to run, in the order they run within each stage.
+///
+/// Tests must live for the whole program. A unit struct can be registered
+/// directly (`registry.register(&MyTest)`), anything else can be leaked
+/// with `Box::leak`.
+#[derive(Clone, Default)]
+pub struct Registry {
+    tests: Vec<TestItem>,
+}
+
+impl Registry {
+    /// An empty registry
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// A registry of all the built-in tests
+    pub fn builtin() -> Registry {
+        let mut registry = Registry::new();
+        for test in crate::tests::builtin() {
+            registry
+                .register(test)
+                .expect("Built-in test ids are unique");
+        }
+        registry
+    }
+
+    pub fn register(&mut self, test: &'static dyn RelayTest) -> Result<(), Error> {
+        if self.get(test.id()).is_some() {
+            return Err(Error::DuplicateTest(test.id()));
+        }
+        self.tests.push(TestItem::new(test));
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = TestItem> + '_ {
+        self.tests.iter().copied()
+    }
+
+    pub fn get(&self, id: &str) -> Option<TestItem> {
+        self.iter().find(|t| t.id() == id)
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.tests.iter()).finish()
+    }
+}
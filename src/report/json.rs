@@ -0,0 +1,30 @@
+use super::{Reporter, Verdict};
+use crate::outcome::Outcome;
+use crate::test_item::TestItem;
+use std::io::Write;
+
+/// One JSON object per line, per test (the `--script` output)
+pub struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn report(&self, results: &[(TestItem, Outcome)], out: &mut dyn Write) -> std::io::Result<()> {
+        for (test_item, outcome) in results {
+            // As in the summary, tests that are not yet implemented are not shown
+            if Verdict::of(*test_item, outcome) == Verdict::NotImplemented {
+                continue;
+            }
+
+            let value = serde_json::json!({
+                "test": test_item.name(),
+                "required": test_item.required(),
+                "pass": outcome.status.passed(),
+                "status": outcome.status.as_str(),
+                "info": outcome.info,
+                "subs": outcome.sub_names(),
+                "duration_ms": outcome.duration.as_millis() as u64,
+            });
+            writeln!(out, "{}", value)?;
+        }
+        Ok(())
+    }
+}
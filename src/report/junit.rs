@@ -0,0 +1,115 @@
+use super::{Reporter, Verdict};
+use crate::outcome::{Outcome, Status};
+use crate::test_item::TestItem;
+use std::io::Write;
+use std::time::Duration;
+
+/// JUnit XML, as understood by most CI systems
+pub struct JunitReporter;
+
+impl Reporter for JunitReporter {
+    fn report(&self, results: &[(TestItem, Outcome)], out: &mut dyn Write) -> std::io::Result<()> {
+        let verdicts: Vec<Verdict> = results.iter().map(|(t, o)| Verdict::of(*t, o)).collect();
+        let count = |v: Verdict| verdicts.iter().filter(|x| **x == v).count();
+        let skipped =
+            count(Verdict::Unsupported) + count(Verdict::Skipped) + count(Verdict::NotImplemented);
+        let total_time: Duration = results.iter().map(|(_, o)| o.duration).sum();
+
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<testsuites name="relay-tester" tests="{}" failures="{}" errors="{}" skipped="{}" time="{:.3}">"#,
+            results.len(),
+            count(Verdict::Failed),
+            count(Verdict::Errored),
+            skipped,
+            total_time.as_secs_f64()
+        )?;
+        writeln!(
+            out,
+            r#"  <testsuite name="relay-tester" tests="{}" failures="{}" errors="{}" skipped="{}" time="{:.3}">"#,
+            results.len(),
+            count(Verdict::Failed),
+            count(Verdict::Errored),
+            skipped,
+            total_time.as_secs_f64()
+        )?;
+
+        for ((test_item, outcome), verdict) in results.iter().zip(verdicts.iter()) {
+            let info = outcome.info.as_deref().unwrap_or("");
+            writeln!(
+                out,
+                r#"    <testcase classname="relay-tester.{}" name="{}" time="{:.3}">"#,
+                test_item.module(),
+                escape(test_item.name()),
+                outcome.duration.as_secs_f64()
+            )?;
+            match verdict {
+                Verdict::Passed => {}
+                Verdict::Failed => writeln!(
+                    out,
+                    r#"      <failure message="{}">{}</failure>"#,
+                    escape(info),
+                    escape(info)
+                )?,
+                Verdict::Unsupported => writeln!(
+                    out,
+                    r#"      <skipped message="Optional: {}"/>"#,
+                    escape(if info.is_empty() {
+                        "not supported"
+                    } else {
+                        info
+                    })
+                )?,
+                Verdict::Skipped => {
+                    let reason = match &outcome.status {
+                        Status::Skipped(reason) => reason.as_str(),
+                        _ => "",
+                    };
+                    writeln!(out, r#"      <skipped message="{}"/>"#, escape(reason))?
+                }
+                Verdict::NotImplemented => {
+                    writeln!(out, r#"      <skipped message="Not yet implemented"/>"#)?
+                }
+                Verdict::Errored => writeln!(
+                    out,
+                    r#"      <error type="{}" message="{}">{}</error>"#,
+                    outcome.status.as_str(),
+                    escape(info),
+                    escape(info)
+                )?,
+            }
+            if !outcome.subs.is_empty() {
+                writeln!(
+                    out,
+                    "      <system-out>subscriptions: {}</system-out>",
+                    outcome.sub_names().join(" ")
+                )?;
+            }
+            writeln!(out, "    </testcase>")?;
+        }
+
+        writeln!(out, "  </testsuite>")?;
+        writeln!(out, "</testsuites>")?;
+        Ok(())
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            // Characters that are not allowed in XML 1.0 at all
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {
+                output.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => output.push(c),
+        }
+    }
+    output
+}
@@ -0,0 +1,78 @@
+mod json;
+mod junit;
+mod tap;
+
+pub use json::JsonReporter;
+pub use junit::JunitReporter;
+pub use tap::TapReporter;
+
+use crate::outcome::{Outcome, Status};
+use crate::test_item::TestItem;
+use std::io::Write;
+
+/// Writes the results of a run in some machine-readable format
+pub trait Reporter {
+    fn report(&self, results: &[(TestItem, Outcome)], out: &mut dyn Write) -> std::io::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Json,
+    Junit,
+    Tap,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Option<Format> {
+        match &*s.to_lowercase() {
+            "json" => Some(Format::Json),
+            "junit" => Some(Format::Junit),
+            "tap" => Some(Format::Tap),
+            _ => None,
+        }
+    }
+
+    pub fn reporter(&self) -> Box<dyn Reporter> {
+        match *self {
+            Format::Json => Box::new(JsonReporter),
+            Format::Junit => Box::new(JunitReporter),
+            Format::Tap => Box::new(TapReporter),
+        }
+    }
+}
+
+/// How a result should be presented to a CI system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    /// Passed (or an optional feature that is supported)
+    Passed,
+
+    /// A required test failed
+    Failed,
+
+    /// An optional feature is not supported
+    Unsupported,
+
+    /// The test was skipped on purpose
+    Skipped,
+
+    /// The test is not yet implemented
+    NotImplemented,
+
+    /// The test could not be run (tester error or failed prerequisite)
+    Errored,
+}
+
+impl Verdict {
+    pub(crate) fn of(test_item: TestItem, outcome: &Outcome) -> Verdict {
+        match (&outcome.status, test_item.required()) {
+            (Status::Pass, _) => Verdict::Passed,
+            (Status::Fail, true) => Verdict::Failed,
+            (Status::Fail, false) => Verdict::Unsupported,
+            (Status::Skipped(_), _) => Verdict::Skipped,
+            (Status::NotImplemented, _) => Verdict::NotImplemented,
+            (Status::TesterError, _) | (Status::PrerequisiteFailed, _) => Verdict::Errored,
+        }
+    }
+}
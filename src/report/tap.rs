@@ -0,0 +1,55 @@
+use super::{Reporter, Verdict};
+use crate::outcome::{Outcome, Status};
+use crate::test_item::TestItem;
+use std::io::Write;
+
+/// Test Anything Protocol, version 13
+pub struct TapReporter;
+
+impl Reporter for TapReporter {
+    fn report(&self, results: &[(TestItem, Outcome)], out: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(out, "TAP version 13")?;
+        writeln!(out, "1..{}", results.len())?;
+
+        for (n, (test_item, outcome)) in results.iter().enumerate() {
+            let n = n + 1;
+            let name = test_item.name().replace('#', "\\#");
+            match Verdict::of(*test_item, outcome) {
+                Verdict::Passed => writeln!(out, "ok {} - {}", n, name)?,
+                Verdict::Failed => writeln!(out, "not ok {} - {}", n, name)?,
+                Verdict::Unsupported => {
+                    writeln!(out, "ok {} - {} # SKIP optional, not supported", n, name)?
+                }
+                Verdict::Skipped => {
+                    let reason = match &outcome.status {
+                        Status::Skipped(reason) => reason.clone(),
+                        _ => String::new(),
+                    };
+                    writeln!(out, "ok {} - {} # SKIP {}", n, name, reason)?
+                }
+                Verdict::NotImplemented => {
+                    writeln!(out, "not ok {} - {} # TODO not yet implemented", n, name)?
+                }
+                Verdict::Errored => writeln!(out, "not ok {} - {}", n, name)?,
+            }
+
+            // YAML diagnostics block
+            writeln!(out, "  ---")?;
+            writeln!(out, "  required: {}", test_item.required())?;
+            if let Some(info) = &outcome.info {
+                writeln!(out, "  message: {}", serde_json::Value::from(info.as_str()))?;
+            }
+            writeln!(out, "  status: {}", outcome.status.as_str())?;
+            if outcome.status.passed().is_none() {
+                writeln!(out, "  severity: error")?;
+            }
+            if !outcome.subs.is_empty() {
+                writeln!(out, "  subs: [{}]", outcome.sub_names().join(", "))?;
+            }
+            writeln!(out, "  duration_ms: {}", outcome.duration.as_millis())?;
+            writeln!(out, "  ...")?;
+        }
+
+        Ok(())
+    }
+}
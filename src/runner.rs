@@ -0,0 +1,226 @@
+use crate::config::{Config, Expectation};
+use crate::context::RunContext;
+use crate::error::Error;
+use crate::outcome::{Outcome, Status};
+use crate::registry::Registry;
+use crate::selection::Selection;
+use crate::stage::Stage;
+use crate::test_item::TestItem;
+use colorful::{Color, Colorful};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use strum::IntoEnumIterator;
+
+/// Runs the selected tests against a relay. Each run has its own
+/// [`RunContext`], so several may be in progress at once.
+#[derive(Debug, Clone)]
+pub struct Runner {
+    pub config: Config,
+    pub selection: Selection,
+    pub registry: Registry,
+}
+
+/// The results of a run
+#[derive(Debug, Clone)]
+pub struct RunResults {
+    /// The outcome of every selected test, in the order they ran
+    pub results: Vec<(TestItem, Outcome)>,
+
+    pub summary: Summary,
+}
+
+/// Counts of test outcomes, taking the configured expectations into account
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub total: usize,
+
+    /// Required tests (or tests expected to pass) that failed
+    pub fail: usize,
+
+    /// Optional tests that failed
+    pub optional_fail: usize,
+
+    pub skipped: usize,
+    pub not_implemented: usize,
+
+    /// Tests that errored in the tester
+    pub untested: usize,
+
+    pub prerequisite_failed: usize,
+
+    /// Tests expected to fail that did
+    pub expected_fail: usize,
+
+    /// Tests expected to fail that passed
+    pub unexpected_pass: usize,
+}
+
+impl Runner {
+    /// A runner for all the built-in tests. Set `selection` to run fewer,
+    /// or register more tests in `registry`.
+    pub fn new(config: Config) -> Runner {
+        Runner {
+            config,
+            selection: Selection::default(),
+            registry: Registry::builtin(),
+        }
+    }
+
+    pub async fn run(&self) -> Result<RunResults, Error> {
+        // Install crypto provider (unless the caller already has)
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        self.config.check_expected(&self.registry)?;
+
+        let mut ctx = RunContext::new(self.config.clone()).await?;
+
+        let to_run = self.selection.tests_to_run(&self.registry);
+        let mut results: Vec<(TestItem, Outcome)> = Vec::new();
+
+        // Run the tests in stages
+        for stage in Stage::iter() {
+            // Skip stages (including their setup) that have nothing to run
+            if !to_run.iter().any(|t| t.stage() == stage) {
+                continue;
+            }
+
+            log!("-----------------------------------------------------");
+            log!(
+                "*** Stage: {} ***",
+                format!("{:?}", stage).color(Color::Green3a)
+            );
+            stage.init(&mut ctx).await?;
+
+            let mut old_next_sub_id = ctx.connection.next_sub_id.load(Ordering::Relaxed);
+
+            for test_item in self.registry.iter() {
+                if test_item.stage() == stage && to_run.contains(&test_item) {
+                    log!("\n--* TEST: {} *--------", test_item.name());
+
+                    // Drop frames from stage setup and earlier tests
+                    let _ = ctx.connection.take_transcript();
+
+                    let start = Instant::now();
+                    let mut outcome = if stage == Stage::Unknown {
+                        Outcome::skipped("Test has not been assigned to a stage yet.".to_owned())
+                    } else {
+                        test_item.run(&mut ctx).await
+                    };
+                    outcome.duration = start.elapsed();
+                    outcome.transcript = ctx.connection.take_transcript();
+                    outcome.transcript.merge(ctx.connections.close_all().await);
+
+                    let new_next_sub_id = ctx.connection.next_sub_id.load(Ordering::Relaxed);
+
+                    // old=5, new=7:   answer=(5,6)
+                    for i in old_next_sub_id..new_next_sub_id {
+                        outcome.subs.push(i);
+                    }
+
+                    ctx.test_results.insert(test_item, outcome.clone());
+                    results.push((test_item, outcome));
+
+                    old_next_sub_id = new_next_sub_id;
+                }
+
+                std::thread::sleep(Duration::new(0, 100));
+            }
+        }
+
+        ctx.connection.disconnect().await?;
+
+        if let Some(ref dir) = self.config.transcript_dir {
+            save_transcripts(dir, &results)?;
+        }
+
+        let summary = Summary::new(&results, &self.config);
+
+        // Display the results
+        log!("====================================================");
+        log!("SUMMARY RESULTS\n");
+
+        for (test_item, outcome) in results.iter() {
+            // Don't print the tests that are not yet implemented
+            if outcome.status == Status::NotImplemented {
+                continue;
+            }
+
+            log!(
+                "{}: {}",
+                test_item.name(),
+                outcome.display(test_item.required())
+            );
+        }
+
+        summary.log();
+
+        Ok(RunResults { results, summary })
+    }
+}
+
+/// Write the transcript of each failing test to `<dir>/<TestId>.txt`
+fn save_transcripts(dir: &str, results: &[(TestItem, Outcome)]) -> Result<(), Error> {
+    std::fs::create_dir_all(dir)?;
+    for (test_item, outcome) in results.iter() {
+        if outcome.is_failure() {
+            let path = Path::new(dir).join(format!("{}.txt", test_item.id()));
+            outcome.transcript.save(path)?;
+        }
+    }
+    Ok(())
+}
+
+impl Summary {
+    pub fn new(results: &[(TestItem, Outcome)], config: &Config) -> Summary {
+        let mut summary = Summary::default();
+
+        for (test_item, outcome) in results.iter() {
+            summary.total += 1;
+
+            let expectation = config.expectation(*test_item);
+
+            match outcome.status {
+                Status::Pass => {
+                    if expectation == Some(Expectation::Fail) {
+                        summary.unexpected_pass += 1;
+                    }
+                }
+                Status::Fail => {
+                    if expectation == Some(Expectation::Fail) {
+                        summary.expected_fail += 1;
+                    } else if test_item.required() || expectation == Some(Expectation::Pass) {
+                        summary.fail += 1;
+                    } else {
+                        summary.optional_fail += 1;
+                    }
+                }
+                Status::Skipped(_) => summary.skipped += 1,
+                Status::NotImplemented => summary.not_implemented += 1,
+                Status::TesterError => summary.untested += 1,
+                Status::PrerequisiteFailed => summary.prerequisite_failed += 1,
+            }
+        }
+
+        summary
+    }
+
+    pub fn log(&self) {
+        log!(
+            "FAIL: {}, UNTESTED: {}, PREREQUISITE_FAILED: {}, SKIPPED: {}, NOT_IMPLEMENTED: {}, TOTAL: {}",
+            self.fail,
+            self.untested,
+            self.prerequisite_failed,
+            self.skipped,
+            self.not_implemented,
+            self.total
+        );
+        if self.expected_fail > 0 || self.unexpected_pass > 0 {
+            log!(
+                "EXPECTED_FAIL: {}, UNEXPECTED_PASS: {}",
+                self.expected_fail,
+                self.unexpected_pass
+            );
+        }
+    }
+}
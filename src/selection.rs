@@ -0,0 +1,98 @@
+use crate::registry::Registry;
+use crate::stage::Stage;
+use crate::test_item::TestItem;
+use std::collections::BTreeSet;
+use strum::IntoEnumIterator;
+
+/// Something on the command line that picks out one or more tests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    Test(TestItem),
+    Module(&'static str),
+    Stage(Stage),
+}
+
+impl Selector {
+    /// Parse a selector. Test ids, module names and stages are matched
+    /// case-insensitively, e.g. "FindById", "delete" or "registered".
+    pub fn parse(s: &str, registry: &Registry) -> Option<Selector> {
+        if let Some(test_item) = registry.iter().find(|t| t.id().eq_ignore_ascii_case(s)) {
+            return Some(Selector::Test(test_item));
+        }
+
+        if let Some(module) = registry
+            .iter()
+            .map(|t| t.module())
+            .find(|m| m.eq_ignore_ascii_case(s))
+        {
+            return Some(Selector::Module(module));
+        }
+
+        if let Some(stage) = Stage::iter().find(|st| format!("{:?}", st).eq_ignore_ascii_case(s)) {
+            return Some(Selector::Stage(stage));
+        }
+
+        None
+    }
+
+    pub fn matches(&self, test_item: TestItem) -> bool {
+        match self {
+            Selector::Test(t) => *t == test_item,
+            Selector::Module(m) => test_item.module() == *m,
+            Selector::Stage(st) => test_item.stage() == *st,
+        }
+    }
+}
+
+/// Which tests to run, from the `--only` and `--skip` options
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    pub only: Vec<Selector>,
+    pub skip: Vec<Selector>,
+}
+
+impl Selection {
+    /// Parse a comma separated list of selectors, returning the first one
+    /// that could not be understood as an error.
+    pub fn parse_list(list: &str, registry: &Registry) -> Result<Vec<Selector>, String> {
+        let mut output: Vec<Selector> = Vec::new();
+        for part in list.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            match Selector::parse(part, registry) {
+                Some(selector) => output.push(selector),
+                None => return Err(part.to_owned()),
+            }
+        }
+        Ok(output)
+    }
+
+    /// Whether the user asked for this test
+    pub fn is_selected(&self, test_item: TestItem) -> bool {
+        if !self.only.is_empty() && !self.only.iter().any(|s| s.matches(test_item)) {
+            return false;
+        }
+        !self.skip.iter().any(|s| s.matches(test_item))
+    }
+
+    /// The tests to run: those selected plus their prerequisites (which are
+    /// pulled in even if they were skipped, else the selected test can't work)
+    pub fn tests_to_run(&self, registry: &Registry) -> BTreeSet<TestItem> {
+        let mut to_run: BTreeSet<TestItem> =
+            registry.iter().filter(|t| self.is_selected(*t)).collect();
+
+        let mut pending: Vec<TestItem> = to_run.iter().copied().collect();
+        while let Some(test_item) = pending.pop() {
+            // Prerequisites that aren't registered can't be run
+            for prereq in test_item
+                .prerequisites()
+                .iter()
+                .filter_map(|id| registry.get(id))
+            {
+                if to_run.insert(prereq) {
+                    pending.push(prereq);
+                }
+            }
+        }
+
+        to_run
+    }
+}
@@ -1,6 +1,6 @@
+use crate::connection::AuthState;
+use crate::context::RunContext;
 use crate::error::Error;
-use crate::globals::{User, GLOBALS};
-use std::time::Duration;
 use strum_macros::{EnumCount, EnumIter};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumCount, EnumIter)]
@@ -13,53 +13,38 @@ pub enum Stage {
 }
 
 impl Stage {
-    pub async fn init(&self) -> Result<(), Error> {
+    pub async fn init(&self, ctx: &mut RunContext) -> Result<(), Error> {
         match *self {
             Stage::Preauth => {
                 // nothing to setup
             }
             Stage::Registered => {
-                GLOBALS
-                    .connection
-                    .write()
-                    .as_mut()
-                    .unwrap()
-                    .authenticate_if_challenged(User::Registered1)
+                // Preauth tests usually read the challenge, but may not have run
+                if ctx.connection.auth_state == AuthState::NotYetRequested {
+                    let _ = ctx
+                        .connection
+                        .wait_for_message(ctx.timeouts().auth())
+                        .await?;
+                }
+
+                ctx.connection
+                    .authenticate_if_challenged(&ctx.registered1)
                     .await?;
 
                 // TBD: Inject Event Group A
             }
             Stage::Stranger => {
-                GLOBALS
-                    .connection
-                    .write()
-                    .as_mut()
-                    .unwrap()
-                    .disconnect()
-                    .await?;
+                ctx.connection.disconnect().await?;
 
-                GLOBALS
-                    .connection
-                    .write()
-                    .as_mut()
-                    .unwrap()
-                    .reconnect()
-                    .await?;
+                ctx.connection.reconnect().await?;
 
-                let _ = GLOBALS
+                let _ = ctx
                     .connection
-                    .write()
-                    .as_mut()
-                    .unwrap()
-                    .wait_for_message(Duration::from_secs(1))
+                    .wait_for_message(ctx.timeouts().auth())
                     .await?;
 
-                GLOBALS
-                    .connection
-                    .write()
-                    .as_mut()
-                    .unwrap()
-                    .authenticate_if_challenged(User::Stranger)
+                ctx.connection
+                    .authenticate_if_challenged(&ctx.stranger)
                     .await?;
             }
             Stage::Unknown => {
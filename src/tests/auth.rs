@@ -1,27 +1,72 @@
 use super::tags;
 use crate::connection::AuthState;
+use crate::context::{EventParts, RunContext, User};
 use crate::error::Error;
-use crate::globals::{EventParts, Globals, User, GLOBALS};
 use crate::outcome::Outcome;
-use crate::WAIT;
 use nostr_types::{EventKind, Tag, Unixtime};
 use std::time::Duration;
 
-pub async fn prompts_for_auth_initially() -> Result<Outcome, Error> {
+relay_test! {
+    PromptsForAuthInitially {
+        name: "Prompts for AUTH when client connects",
+        nips: [42],
+        required: false,
+        stage: Preauth,
+        run: prompts_for_auth_initially,
+    }
+
+    AuthEventKindVerified {
+        name: "Auth event kind is verified",
+        nips: [42],
+        required: true,
+        stage: Preauth,
+        run: kind_verified,
+    }
+
+    AuthEventRelayVerified {
+        name: "Auth event relay is verified",
+        nips: [42],
+        required: true,
+        stage: Preauth,
+        run: relay_verified,
+    }
+
+    AuthEventChallengeVerified {
+        name: "Auth event challenge is verified",
+        nips: [42],
+        required: true,
+        stage: Preauth,
+        run: challenge_verified,
+    }
+
+    AuthEventTimeVerified {
+        name: "Auth event time is verified",
+        nips: [42],
+        required: true,
+        stage: Preauth,
+        run: time_verified,
+    }
+
+    CanAuthAsUnknown {
+        name: "Can AUTH as unknown",
+        nips: [42],
+        required: false,
+        stage: Stranger,
+        run: can_auth_as_unknown,
+    }
+}
+
+pub async fn prompts_for_auth_initially(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // Wait for AUTH message first
     // NOTE: auth_state will be internally updated during the wait
-    {
-        let mut con = GLOBALS.connection.write();
-        con.as_mut().unwrap().disconnect().await?;
-        con.as_mut().unwrap().reconnect().await?;
-        let _ = con
-            .as_mut()
-            .unwrap()
-            .wait_for_message(Duration::from_secs(WAIT))
-            .await?;
-    }
+    ctx.connection.disconnect().await?;
+    ctx.connection.reconnect().await?;
+    let _ = ctx
+        .connection
+        .wait_for_message(ctx.timeouts().wait())
+        .await?;
 
-    let outcome = match &GLOBALS.connection.read().as_ref().unwrap().auth_state {
+    let outcome = match &ctx.connection.auth_state {
         AuthState::NotYetRequested => Outcome::fail(Some("Did not prompt us for AUTH".to_owned())),
         AuthState::Challenged(_) => Outcome::pass(None),
         s => Outcome::fail(Some(format!(
@@ -33,23 +78,25 @@ pub async fn prompts_for_auth_initially() -> Result<Outcome, Error> {
     Ok(outcome)
 }
 
-pub async fn kind_verified() -> Result<Outcome, Error> {
-    let mut con = GLOBALS.connection.write();
-    con.as_mut().unwrap().disconnect().await?;
-    con.as_mut().unwrap().reconnect().await?;
-    let _ = con
-        .as_mut()
-        .unwrap()
-        .wait_for_message(Duration::from_secs(WAIT))
+pub async fn kind_verified(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    ctx.connection.disconnect().await?;
+    ctx.connection.reconnect().await?;
+    let _ = ctx
+        .connection
+        .wait_for_message(ctx.timeouts().wait())
         .await?;
 
     // Trigger AUTH challenge
-    if let Some(challenge) = con.as_mut().unwrap().trigger_auth_get_challenge().await? {
-        let event = Globals::make_event(
+    if let Some(challenge) = ctx
+        .connection
+        .trigger_auth_get_challenge(&ctx.stranger)
+        .await?
+    {
+        let event = ctx.make_event(
             EventParts::Basic(
                 EventKind::TextNote, // <-- Intentionally wrong kind
                 vec![
-                    Tag::new(&["relay", &*GLOBALS.relay_url.read()]),
+                    Tag::new(&["relay", &ctx.relay_url]),
                     Tag::new(&["challenge", &*challenge]),
                 ],
                 "".to_string(),
@@ -57,37 +104,38 @@ pub async fn kind_verified() -> Result<Outcome, Error> {
             User::Registered1,
         )?;
 
-        con.as_mut()
-            .unwrap()
+        ctx.connection
             .authenticate_if_challenged_with_event(event)
             .await?;
 
-        match con.as_mut().unwrap().auth_state {
+        match ctx.connection.auth_state {
             AuthState::Failure(_) => Ok(Outcome::pass(None)),
             AuthState::Success => Ok(Outcome::fail(None)),
             _ => Ok(Outcome::err("Could not get AUTH to work".to_owned())),
         }
     } else {
         // We cannot test this if we are not AUTH challenged
-        Ok(Outcome::err(
+        Ok(Outcome::skipped(
             "Cannot test AUTH, was not challenged".to_owned(),
         ))
     }
 }
 
-pub async fn relay_verified() -> Result<Outcome, Error> {
-    let mut con = GLOBALS.connection.write();
-    con.as_mut().unwrap().disconnect().await?;
-    con.as_mut().unwrap().reconnect().await?;
-    let _ = con
-        .as_mut()
-        .unwrap()
-        .wait_for_message(Duration::from_secs(WAIT))
+pub async fn relay_verified(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    ctx.connection.disconnect().await?;
+    ctx.connection.reconnect().await?;
+    let _ = ctx
+        .connection
+        .wait_for_message(ctx.timeouts().wait())
         .await?;
 
     // Trigger AUTH challenge
-    if let Some(challenge) = con.as_mut().unwrap().trigger_auth_get_challenge().await? {
-        let event = Globals::make_event(
+    if let Some(challenge) = ctx
+        .connection
+        .trigger_auth_get_challenge(&ctx.stranger)
+        .await?
+    {
+        let event = ctx.make_event(
             EventParts::Basic(
                 EventKind::Auth,
                 vec![
@@ -99,41 +147,43 @@ pub async fn relay_verified() -> Result<Outcome, Error> {
             User::Registered1,
         )?;
 
-        con.as_mut()
-            .unwrap()
+        ctx.connection
             .authenticate_if_challenged_with_event(event)
             .await?;
 
-        match con.as_mut().unwrap().auth_state {
+        match ctx.connection.auth_state {
             AuthState::Failure(_) => Ok(Outcome::pass(None)),
             AuthState::Success => Ok(Outcome::fail(None)),
             _ => Ok(Outcome::err("Could not get AUTH to work".to_owned())),
         }
     } else {
         // We cannot test this if we are not AUTH challenged
-        Ok(Outcome::err(
+        Ok(Outcome::skipped(
             "Cannot test AUTH, was not challenged".to_owned(),
         ))
     }
 }
 
-pub async fn challenge_verified() -> Result<Outcome, Error> {
-    let mut con = GLOBALS.connection.write();
-    con.as_mut().unwrap().disconnect().await?;
-    con.as_mut().unwrap().reconnect().await?;
-    let _ = con
-        .as_mut()
-        .unwrap()
-        .wait_for_message(Duration::from_secs(WAIT))
+pub async fn challenge_verified(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    ctx.connection.disconnect().await?;
+    ctx.connection.reconnect().await?;
+    let _ = ctx
+        .connection
+        .wait_for_message(ctx.timeouts().wait())
         .await?;
 
     // Trigger AUTH challenge
-    if (con.as_mut().unwrap().trigger_auth_get_challenge().await?).is_some() {
-        let event = Globals::make_event(
+    if (ctx
+        .connection
+        .trigger_auth_get_challenge(&ctx.stranger)
+        .await?)
+        .is_some()
+    {
+        let event = ctx.make_event(
             EventParts::Basic(
                 EventKind::Auth,
                 vec![
-                    Tag::new(&["relay", &*GLOBALS.relay_url.read()]),
+                    Tag::new(&["relay", &ctx.relay_url]),
                     Tag::new(&["challenge", "intentionally wrong challenge"]),
                 ],
                 "".to_string(),
@@ -141,41 +191,42 @@ pub async fn challenge_verified() -> Result<Outcome, Error> {
             User::Registered1,
         )?;
 
-        con.as_mut()
-            .unwrap()
+        ctx.connection
             .authenticate_if_challenged_with_event(event)
             .await?;
 
-        match con.as_mut().unwrap().auth_state {
+        match ctx.connection.auth_state {
             AuthState::Failure(_) => Ok(Outcome::pass(None)),
             AuthState::Success => Ok(Outcome::fail(None)),
             _ => Ok(Outcome::err("Could not get AUTH to work".to_owned())),
         }
     } else {
         // We cannot test this if we are not AUTH challenged
-        Ok(Outcome::err(
+        Ok(Outcome::skipped(
             "Cannot test AUTH, was not challenged".to_owned(),
         ))
     }
 }
 
-pub async fn time_verified() -> Result<Outcome, Error> {
-    let mut con = GLOBALS.connection.write();
-    con.as_mut().unwrap().disconnect().await?;
-    con.as_mut().unwrap().reconnect().await?;
-    let _ = con
-        .as_mut()
-        .unwrap()
-        .wait_for_message(Duration::from_secs(WAIT))
+pub async fn time_verified(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    ctx.connection.disconnect().await?;
+    ctx.connection.reconnect().await?;
+    let _ = ctx
+        .connection
+        .wait_for_message(ctx.timeouts().wait())
         .await?;
 
     // Trigger AUTH challenge
-    if let Some(challenge) = con.as_mut().unwrap().trigger_auth_get_challenge().await? {
-        let event = Globals::make_event(
+    if let Some(challenge) = ctx
+        .connection
+        .trigger_auth_get_challenge(&ctx.stranger)
+        .await?
+    {
+        let event = ctx.make_event(
             EventParts::Dated(
                 EventKind::Auth,
                 vec![
-                    Tag::new(&["relay", &*GLOBALS.relay_url.read()]),
+                    Tag::new(&["relay", &ctx.relay_url]),
                     Tag::new(&["challenge", &*challenge]),
                 ],
                 "".to_string(),
@@ -185,58 +236,47 @@ pub async fn time_verified() -> Result<Outcome, Error> {
             User::Registered1,
         )?;
 
-        con.as_mut()
-            .unwrap()
+        ctx.connection
             .authenticate_if_challenged_with_event(event)
             .await?;
 
-        match con.as_mut().unwrap().auth_state {
+        match ctx.connection.auth_state {
             AuthState::Failure(_) => Ok(Outcome::pass(None)),
             AuthState::Success => Ok(Outcome::fail(None)),
             _ => Ok(Outcome::err("Could not get AUTH to work".to_owned())),
         }
     } else {
         // We cannot test this if we are not AUTH challenged
-        Ok(Outcome::err(
+        Ok(Outcome::skipped(
             "Cannot test AUTH, was not challenged".to_owned(),
         ))
     }
 }
 
-pub async fn can_auth_as_unknown() -> Result<Outcome, Error> {
+pub async fn can_auth_as_unknown(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // Restart the connection
-    {
-        let mut con = GLOBALS.connection.write();
-        con.as_mut().unwrap().disconnect().await?;
-        con.as_mut().unwrap().reconnect().await?;
-    }
+    ctx.connection.disconnect().await?;
+    ctx.connection.reconnect().await?;
 
     // Try to post something (to trigger AUTH if it isn't automatic)
     // but ignore any result/error
     {
-        let event = Globals::make_event(
+        let event = ctx.make_event(
             EventParts::Basic(EventKind::TextNote, tags(&[&["test"]]), "".to_string()),
             User::Stranger,
         )?;
-        let _ = GLOBALS
+        let _ = ctx
             .connection
-            .write()
-            .as_mut()
-            .unwrap()
-            .post_event(event, Duration::from_secs(WAIT))
+            .post_event(event, ctx.timeouts().wait())
             .await?;
     }
 
     // Reply to the AUTH challenge with the Stranger
-    GLOBALS
-        .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .authenticate_if_challenged(User::Stranger)
+    ctx.connection
+        .authenticate_if_challenged(&ctx.stranger)
         .await?;
 
-    match &GLOBALS.connection.read().as_ref().unwrap().auth_state {
+    match &ctx.connection.auth_state {
         AuthState::Success => Ok(Outcome::pass(None)),
         AuthState::Failure(s) => Ok(Outcome::fail(Some(s.to_owned()))),
         AuthState::InProgress(_) => Ok(Outcome::fail(Some("Did not complete AUTH".to_owned()))),
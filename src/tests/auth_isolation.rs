@@ -0,0 +1,300 @@
+use super::nip11::claims_nip;
+use super::tags;
+use crate::connection::{AuthState, Connection, Reason, ReasonPrefix};
+use crate::connections::Role;
+use crate::context::{EventParts, RunContext, User};
+use crate::error::Error;
+use crate::outcome::Outcome;
+use nostr_types::{Event, EventKind, Filter, KeySigner, Signer};
+use std::time::Duration;
+
+// An AUTH speaks for the connection it was sent on, and no other. These
+// hold several connections at once, each with its own identity (or none).
+relay_test! {
+    GiftwrapsRequireAuth {
+        name: "Giftwraps require AUTH",
+        nips: [42, 59],
+        required: true,
+        stage: Stranger,
+        prerequisites: [Nip11Provided],
+        run: giftwraps_require_auth,
+    }
+
+    Nip4DmsRequireAuth {
+        name: "Nip-04 DMs require AUTH",
+        nips: [4, 42],
+        required: false,
+        stage: Stranger,
+        prerequisites: [Nip11Provided],
+        run: nip4_dms_require_auth,
+    }
+
+    Nip4DmWritesRequireAuth {
+        name: "Nip-04 DMs are only accepted after AUTH",
+        nips: [4, 42],
+        required: false,
+        stage: Stranger,
+        prerequisites: [Nip11Provided],
+        run: nip4_dm_writes_require_auth,
+    }
+
+    SecondAuthOnConnection {
+        name: "Handles a second AUTH on one connection",
+        nips: [42],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: second_auth_on_connection,
+    }
+}
+
+pub async fn giftwraps_require_auth(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    served_only_to_recipient(ctx, EventKind::GiftWrap).await
+}
+
+pub async fn nip4_dms_require_auth(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    served_only_to_recipient(ctx, EventKind::EncryptedDirectMessage).await
+}
+
+// Post a DM for Registered1 on a connection that never authenticates, then
+// the same DM on one authenticated as its author. Only the second should be
+// accepted.
+pub async fn nip4_dm_writes_require_auth(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    if let Some(outcome) = skip_without_auth(ctx) {
+        return Ok(outcome);
+    }
+
+    let event = private_event(ctx, EventKind::EncryptedDirectMessage, User::Registered1)?;
+    let wait = ctx.timeouts().wait();
+
+    let anonymous = ctx.connect("anonymous", Role::Anonymous).await?;
+    let (ok, reason) = anonymous.post_event(event.clone(), wait).await?;
+    if ok {
+        return Ok(Outcome::fail(Some(
+            "Accepted a DM on a connection that had not authenticated".to_owned(),
+        )));
+    }
+    if Reason::parse(&reason).prefix != Some(ReasonPrefix::AuthRequired) {
+        return Ok(Outcome::fail(Some(format!(
+            "Refused a DM without auth-required: {}",
+            reason
+        ))));
+    }
+
+    // Authenticated as its author (if need be, once the relay asks)
+    ctx.connect("sender", Role::As(User::Registered2)).await?;
+    let sender = ctx.connections.get("sender");
+    let (mut ok, mut reason) = sender.post_event(event.clone(), wait).await?;
+    if !ok && matches!(sender.auth_state, AuthState::Challenged(_)) {
+        sender.authenticate_if_challenged(&ctx.registered2).await?;
+        (ok, reason) = sender.post_event(event, wait).await?;
+    }
+
+    if ok {
+        Ok(Outcome::pass(None))
+    } else {
+        Ok(Outcome::fail(Some(format!(
+            "Refused the DM after AUTH too: {}",
+            reason
+        ))))
+    }
+}
+
+pub async fn second_auth_on_connection(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    if let Some(outcome) = skip_without_auth(ctx) {
+        return Ok(outcome);
+    }
+
+    let for_first = private_event(ctx, EventKind::GiftWrap, User::Registered1)?;
+    let for_second = private_event(ctx, EventKind::GiftWrap, User::Registered2)?;
+    for event in [&for_first, &for_second] {
+        if let Err(outcome) = send(ctx, event).await? {
+            return Ok(outcome);
+        }
+    }
+
+    let first_filter = addressed_to(ctx, EventKind::GiftWrap, User::Registered1);
+    let second_filter = addressed_to(ctx, EventKind::GiftWrap, User::Registered2);
+    let wait = ctx.timeouts().wait();
+    ctx.connect("both", Role::As(User::Registered1)).await?;
+    let both = ctx.connections.get("both");
+
+    // Authenticated as Registered1 (if need be, once the relay asks)
+    let first = served(
+        both,
+        Some(&ctx.registered1),
+        &first_filter,
+        &for_first,
+        wait,
+    )
+    .await?;
+    if !first || both.auth_state != AuthState::Success {
+        return Ok(Outcome::prerequisite_failed(
+            "Could not AUTH and read our giftwraps".to_owned(),
+        ));
+    }
+
+    both.authenticate_again(&ctx.registered2).await?;
+    let refused = match both.auth_state.clone() {
+        AuthState::Success => None,
+        AuthState::Failure(reason) if Reason::parse(&reason).prefix.is_some() => Some(reason),
+        AuthState::Failure(reason) => {
+            return Ok(Outcome::fail(Some(format!(
+                "Refused a second AUTH without a standard reason: {}",
+                reason
+            ))))
+        }
+        AuthState::InProgress(_) => {
+            return Ok(Outcome::fail(Some("No OK for a second AUTH".to_owned())))
+        }
+        s => return Ok(Outcome::err(format!("Unexpected auth state: {:?}", s))),
+    };
+
+    // A relay may let one connection speak for several pubkeys, or refuse
+    // to, but must not forget the first
+    let first = served(both, None, &first_filter, &for_first, wait).await?;
+    let second = served(both, None, &second_filter, &for_second, wait).await?;
+    let outcome = match (first, second, refused) {
+        (false, _, _) => Outcome::fail(Some(
+            "Stopped serving the first pubkey after a second AUTH".to_owned(),
+        )),
+        (true, true, None) => Outcome::pass(Some("Authenticated as both".to_owned())),
+        (true, false, None) => Outcome::fail(Some(
+            "Accepted a second AUTH, but does not serve its giftwraps".to_owned(),
+        )),
+        (true, true, Some(reason)) => Outcome::fail(Some(format!(
+            "Refused a second AUTH ({}), but serves its giftwraps anyway",
+            reason
+        ))),
+        (true, false, Some(reason)) => Outcome::pass(Some(reason)),
+    };
+    Ok(outcome)
+}
+
+// Send a private event for Registered1, then ask for it on one connection
+// authenticated as Registered1, one authenticated as the stranger and one
+// that never authenticates. Only the first should be served it.
+async fn served_only_to_recipient(ctx: &mut RunContext, kind: EventKind) -> Result<Outcome, Error> {
+    if let Some(outcome) = skip_without_auth(ctx) {
+        return Ok(outcome);
+    }
+
+    let event = private_event(ctx, kind, User::Registered1)?;
+    if let Err(outcome) = send(ctx, &event).await? {
+        return Ok(outcome);
+    }
+
+    let filter = addressed_to(ctx, kind, User::Registered1);
+    let wait = ctx.timeouts().wait();
+    ctx.connect("recipient", Role::As(User::Registered1))
+        .await?;
+    ctx.connect("stranger", Role::As(User::Stranger)).await?;
+    ctx.connect("anonymous", Role::Anonymous).await?;
+
+    let recipient = served(
+        ctx.connections.get("recipient"),
+        Some(&ctx.registered1),
+        &filter,
+        &event,
+        wait,
+    )
+    .await?;
+    let stranger = served(
+        ctx.connections.get("stranger"),
+        Some(&ctx.stranger),
+        &filter,
+        &event,
+        wait,
+    )
+    .await?;
+    let anonymous = served(
+        ctx.connections.get("anonymous"),
+        None,
+        &filter,
+        &event,
+        wait,
+    )
+    .await?;
+
+    let outcome = if !recipient {
+        Outcome::fail(Some(
+            "Not served to its recipient, even after AUTH".to_owned(),
+        ))
+    } else if anonymous {
+        Outcome::fail(Some(
+            "Served on a connection that had not authenticated".to_owned(),
+        ))
+    } else if stranger {
+        Outcome::fail(Some(
+            "Served on a connection authenticated as someone else".to_owned(),
+        ))
+    } else {
+        Outcome::pass(None)
+    };
+    Ok(outcome)
+}
+
+fn skip_without_auth(ctx: &RunContext) -> Option<Outcome> {
+    match ctx.nip11 {
+        None => Some(Outcome::skipped("NIP-11 document was not found".to_owned())),
+        Some(ref nip11) if !claims_nip(nip11, 42) => Some(Outcome::skipped(
+            "Relay does not claim to support AUTH".to_owned(),
+        )),
+        _ => None,
+    }
+}
+
+// An event of the kind addressed to the user, from Registered2 (the
+// content is not really encrypted, as the relay can't tell)
+fn private_event(ctx: &RunContext, kind: EventKind, to: User) -> Result<Event, Error> {
+    let to = ctx.signer(to).public_key().as_hex_string();
+    ctx.make_event(
+        EventParts::Basic(kind, tags(&[&["p", &to]]), "Private content".to_owned()),
+        User::Registered2,
+    )
+}
+
+// Post the event from its author's own connection. If the relay rejects
+// it, the outcome to report.
+async fn send(ctx: &mut RunContext, event: &Event) -> Result<Result<(), Outcome>, Error> {
+    let wait = ctx.timeouts().wait();
+    let sender = ctx.connect("sender", Role::As(User::Registered2)).await?;
+    let (ok, reason) = sender.post_event(event.clone(), wait).await?;
+    if ok {
+        Ok(Ok(()))
+    } else {
+        Ok(Err(Outcome::prerequisite_failed(reason)))
+    }
+}
+
+// The filter for private events of the kind addressed to the user
+fn addressed_to(ctx: &RunContext, kind: EventKind, to: User) -> Filter {
+    let mut filter = Filter::new();
+    filter.add_event_kind(kind);
+    filter.add_tag_value('p', ctx.signer(to).public_key().as_hex_string());
+    filter
+}
+
+// Whether the event is served on the connection when asked for with the
+// filter. With a signer, answer an auth-required CLOSED with AUTH and ask
+// again.
+async fn served(
+    conn: &mut Connection,
+    signer: Option<&KeySigner>,
+    filter: &Filter,
+    event: &Event,
+    wait: Duration,
+) -> Result<bool, Error> {
+    let mut result = conn.fetch_events(filter.clone(), wait).await?;
+    if let Some(signer) = signer {
+        let auth_required = result
+            .close_reason()
+            .map(|reason| reason.prefix == Some(ReasonPrefix::AuthRequired))
+            == Some(true);
+        if auth_required && matches!(conn.auth_state, AuthState::Challenged(_)) {
+            conn.authenticate_if_challenged(signer).await?;
+            result = conn.fetch_events(filter.clone(), wait).await?;
+        }
+    }
+    Ok(result.into_events().iter().any(|e| e.id == event.id))
+}
@@ -0,0 +1,186 @@
+use super::limits::limit;
+use crate::connection::{Connection, Reason, ReasonPrefix};
+use crate::connections::Role;
+use crate::context::{RunContext, User};
+use crate::error::Error;
+use crate::outcome::Outcome;
+use nostr_types::RelayMessage;
+use std::fmt;
+use std::time::Duration;
+
+// These ramp up until the relay refuses, to find where its limits are and
+// how it enforces them, when it doesn't say (see also limits.rs)
+relay_test! {
+    MaxSubscriptions {
+        name: "Max subscriptions",
+        nips: [11],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided, LimitMaxSubscriptions],
+        run: max_subscriptions,
+    }
+
+    MaxConnections {
+        name: "Max connections",
+        nips: [11],
+        required: false,
+        stage: Registered,
+        run: max_connections,
+    }
+}
+
+/// The most subscriptions we open on one connection, looking for a limit
+const MAX_SUBSCRIPTIONS_TO_TRY: usize = 256;
+
+/// The most connections we open alongside the main one, looking for a limit
+const MAX_CONNECTIONS_TO_TRY: usize = 64;
+
+// How the relay turned us away
+enum Refusal {
+    // Answered the websocket upgrade with something other than 101
+    Http(u16),
+    Closed(String),
+    Notice(String),
+    Dropped(String),
+    NoAnswer,
+}
+
+impl Refusal {
+    fn from_connect(e: Error) -> Refusal {
+        match e {
+            Error::Websocket(tungstenite::Error::Http(response)) => {
+                Refusal::Http(response.status().as_u16())
+            }
+            Error::Timeout(_) => Refusal::NoAnswer,
+            e => Refusal::Dropped(format!("{}", e)),
+        }
+    }
+}
+
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refusal::Http(status) => write!(f, "HTTP {} on upgrade", status),
+            Refusal::Closed(msg) => write!(f, "CLOSED \"{}\"", msg),
+            Refusal::Notice(msg) => write!(f, "NOTICE \"{}\"", msg),
+            Refusal::Dropped(e) => write!(f, "dropped the connection ({})", e),
+            Refusal::NoAnswer => write!(f, "no answer"),
+        }
+    }
+}
+
+pub async fn max_subscriptions(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    // LimitMaxSubscriptions compares an advertised limit with what the
+    // relay does
+    if let Ok(max) = limit(ctx, "max_subscriptions") {
+        return Ok(Outcome::skipped(format!(
+            "Relay advertises max_subscriptions of {}, which LimitMaxSubscriptions probes",
+            max
+        )));
+    }
+
+    let wait = ctx.timeouts().wait();
+    let subscriber = ctx
+        .connect("subscriber", Role::As(User::Registered1))
+        .await?;
+    let mut opened: usize = 0;
+    let mut refusal: Option<Refusal> = None;
+    while opened < MAX_SUBSCRIPTIONS_TO_TRY {
+        match subscribe(subscriber, wait).await {
+            Ok(()) => opened += 1,
+            Err(r) => {
+                refusal = Some(r);
+                break;
+            }
+        }
+    }
+
+    let outcome = match refusal {
+        None => Outcome::pass(Some(format!(
+            "Kept {} subscriptions open without refusal",
+            opened
+        ))),
+        Some(refusal) if opened == 0 => {
+            Outcome::prerequisite_failed(format!("Refused the first subscription: {}", refusal))
+        }
+        Some(Refusal::NoAnswer) => Outcome::fail(Some(format!(
+            "Stopped answering at subscription {}",
+            opened + 1
+        ))),
+        Some(refusal) => Outcome::pass(Some(format!(
+            "Refused subscription {}: {}",
+            opened + 1,
+            refusal
+        ))),
+    };
+    Ok(outcome)
+}
+
+pub async fn max_connections(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let wait = ctx.timeouts().wait();
+    let mut opened: usize = 0;
+    let mut refusal: Option<Refusal> = None;
+    while opened < MAX_CONNECTIONS_TO_TRY {
+        let name = format!("probe{}", opened);
+        let probe = match ctx.connect(&name, Role::Anonymous).await {
+            Ok(probe) => probe,
+            Err(e) => {
+                refusal = Some(Refusal::from_connect(e));
+                break;
+            }
+        };
+
+        // It counts if the relay answers on it, even if only to ask for AUTH
+        match subscribe(probe, wait).await {
+            Ok(()) => opened += 1,
+            Err(Refusal::Closed(msg))
+                if Reason::parse(&msg).prefix == Some(ReasonPrefix::AuthRequired) =>
+            {
+                opened += 1
+            }
+            Err(r) => {
+                refusal = Some(r);
+                break;
+            }
+        }
+    }
+
+    // Counting our main connection
+    let held = opened + 1;
+    let outcome = match refusal {
+        None => Outcome::pass(Some(format!("Held {} connections without refusal", held))),
+        Some(Refusal::NoAnswer) => Outcome::fail(Some(format!(
+            "Stopped answering at connection {}",
+            held + 1
+        ))),
+        Some(refusal) => Outcome::pass(Some(format!(
+            "Refused connection {}: {}",
+            held + 1,
+            refusal
+        ))),
+    };
+    Ok(outcome)
+}
+
+// Open a subscription that stays open (as it wants future events), and
+// wait for its EOSE
+async fn subscribe(conn: &mut Connection, wait: Duration) -> Result<(), Refusal> {
+    let sub_id = conn.fresh_sub_id();
+    let wire = serde_json::json!(["REQ", sub_id.0, { "kinds": [1], "limit": 0 }]).to_string();
+    if let Err(e) = conn.send_raw_message(wire).await {
+        return Err(Refusal::Dropped(format!("{}", e)));
+    }
+
+    loop {
+        match conn.wait_for_message(wait).await {
+            Ok(Some(RelayMessage::Eose(sub))) if sub == sub_id => return Ok(()),
+            Ok(Some(RelayMessage::Closed(sub, msg))) if sub == sub_id => {
+                return Err(Refusal::Closed(msg))
+            }
+            Ok(Some(RelayMessage::Notice(msg))) => return Err(Refusal::Notice(msg)),
+            Ok(Some(_)) => {}
+            Ok(None) | Err(Error::TimedOut) => return Err(Refusal::NoAnswer),
+            Err(e) => return Err(Refusal::Dropped(format!("{}", e))),
+        }
+    }
+}
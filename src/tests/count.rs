@@ -0,0 +1,255 @@
+use super::maybe_submit_event_group_a;
+use super::nip11::claims_nip;
+use crate::connection::{Count, CountResult};
+use crate::context::RunContext;
+use crate::error::Error;
+use crate::outcome::Outcome;
+use nostr_types::{EventKind, Filter, Id, Signer};
+
+relay_test! {
+    CountMatchesReq {
+        name: "COUNT agrees with REQ",
+        nips: [45],
+        required: false,
+        stage: Registered,
+        run: count_matches_req,
+    }
+
+    CountFiltersAreOred {
+        name: "COUNT with multiple filters counts events matching any",
+        nips: [45],
+        required: false,
+        stage: Registered,
+        run: count_filters_are_ored,
+    }
+
+    CountExactUnlessApproximate {
+        name: "COUNT is exact unless marked approximate",
+        nips: [45],
+        required: false,
+        stage: Registered,
+        run: count_exact_unless_approximate,
+    }
+
+    CountHllIsWellFormed {
+        name: "COUNT hll is well formed",
+        nips: [45],
+        required: false,
+        stage: Registered,
+        run: count_hll_is_well_formed,
+    }
+
+    ClosesUnsupportedCount {
+        name: "Closes COUNT if unsupported",
+        nips: [45],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: closes_unsupported_count,
+    }
+}
+
+pub async fn count_matches_req(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+
+    // Only this run's notes, so that a REQ can't be cut short by a limit
+    let filter = {
+        let mut filter = Filter::new();
+        filter.ids = group_a_ids(ctx);
+        filter.add_author(ctx.registered1.public_key());
+        filter.add_event_kind(EventKind::TextNote);
+        filter
+    };
+
+    let result = ctx
+        .connection
+        .count(vec![filter.clone()], ctx.timeouts().wait())
+        .await?;
+    let count = match answered(result) {
+        Ok(count) => count,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    let fetched = ctx
+        .connection
+        .fetch_events(filter, ctx.timeouts().wait())
+        .await?
+        .into_events()
+        .len() as u64;
+
+    if count.count == fetched {
+        Ok(Outcome::pass(None))
+    } else if count.approximate {
+        Ok(Outcome::pass(Some(format!(
+            "Counted about {}, REQ returned {}",
+            count.count, fetched
+        ))))
+    } else {
+        Ok(Outcome::fail(Some(format!(
+            "Counted {}, but REQ returned {}",
+            count.count, fetched
+        ))))
+    }
+}
+
+pub async fn count_filters_are_ored(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+
+    // Only this run's notes, not those left by earlier runs
+    let ids = group_a_ids(ctx);
+    let filters: Vec<Filter> = ["a", "b"]
+        .iter()
+        .map(|t| {
+            let mut filter = Filter::new();
+            filter.ids = ids.clone();
+            filter.add_author(ctx.registered1.public_key());
+            filter.add_event_kind(EventKind::TextNote);
+            filter.add_tag_value('t', t.to_string());
+            filter
+        })
+        .collect();
+
+    let expected = ctx
+        .event_group_a
+        .iter()
+        .filter(|(e, can_read_back)| *can_read_back && filters.iter().any(|f| f.event_matches(e)))
+        .count() as u64;
+
+    let result = ctx.connection.count(filters, ctx.timeouts().wait()).await?;
+    let count = match answered(result) {
+        Ok(count) => count,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    judge(count, expected)
+}
+
+pub async fn count_exact_unless_approximate(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+
+    // Count Event Group A by id, so we know the right answer
+    let ids = group_a_ids(ctx);
+    let expected = ids.len() as u64;
+
+    let filter = {
+        let mut filter = Filter::new();
+        filter.ids = ids;
+        filter
+    };
+
+    let result = ctx
+        .connection
+        .count(vec![filter], ctx.timeouts().wait())
+        .await?;
+    let count = match answered(result) {
+        Ok(count) => count,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    judge(count, expected)
+}
+
+pub async fn count_hll_is_well_formed(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+
+    // Reactions to an event: the sort of filter NIP-45 suggests HLL for
+    let id = ctx.event_group_a.get("limit_test_first").unwrap().0.id;
+    let filter = {
+        let mut filter = Filter::new();
+        filter.add_event_kind(EventKind::Reaction);
+        filter.add_tag_value('e', id.as_hex_string());
+        filter
+    };
+
+    let result = ctx
+        .connection
+        .count(vec![filter], ctx.timeouts().wait())
+        .await?;
+    let count = match answered(result) {
+        Ok(count) => count,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    match count.hll {
+        None => Ok(Outcome::skipped("Relay did not send hll".to_owned())),
+        Some(hll) => {
+            // 256 one-byte registers
+            if hll.len() == 512 && hll.chars().all(|c| c.is_ascii_hexdigit()) {
+                Ok(Outcome::pass(None))
+            } else {
+                Ok(Outcome::fail(Some(format!(
+                    "hll is not 512 hex digits: {}",
+                    hll
+                ))))
+            }
+        }
+    }
+}
+
+pub async fn closes_unsupported_count(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    match ctx.nip11 {
+        None => return Ok(Outcome::skipped("NIP-11 document was not found".to_owned())),
+        Some(ref nip11) if claims_nip(nip11, 45) => {
+            return Ok(Outcome::skipped("Relay claims to support COUNT".to_owned()))
+        }
+        _ => {}
+    }
+
+    let filter = {
+        let mut filter = Filter::new();
+        filter.add_event_kind(EventKind::TextNote);
+        filter
+    };
+
+    let result = ctx
+        .connection
+        .count(vec![filter], ctx.timeouts().wait())
+        .await?;
+
+    match (result.count, result.close_msg) {
+        (_, Some(msg)) => Ok(Outcome::pass(Some(msg))),
+        (Some(count), None) => Ok(Outcome::pass(Some(format!(
+            "Counted {} without claiming NIP-45",
+            count.count
+        )))),
+        (None, None) => Ok(Outcome::fail(Some(
+            "Timed out rather than closing the COUNT".to_owned(),
+        ))),
+    }
+}
+
+// The count, or the outcome if the relay didn't give one
+fn answered(result: CountResult) -> Result<Count, Outcome> {
+    match (result.count, result.close_msg) {
+        (Some(count), _) => Ok(count),
+        (None, Some(msg)) => Err(Outcome::fail(Some(format!("COUNT was closed: {}", msg)))),
+        (None, None) => Err(Outcome::fail(Some(
+            "Timed out waiting for COUNT".to_owned(),
+        ))),
+    }
+}
+
+fn judge(count: Count, expected: u64) -> Result<Outcome, Error> {
+    if count.count == expected {
+        Ok(Outcome::pass(None))
+    } else if count.approximate {
+        Ok(Outcome::pass(Some(format!(
+            "Counted about {}, expected {}",
+            count.count, expected
+        ))))
+    } else {
+        Ok(Outcome::fail(Some(format!(
+            "Counted {}, expected {}",
+            count.count, expected
+        ))))
+    }
+}
+
+// The ids of the Event Group A events we can read back
+fn group_a_ids(ctx: &RunContext) -> Vec<Id> {
+    ctx.event_group_a
+        .iter()
+        .filter(|v| v.1)
+        .map(|v| v.0.id)
+        .collect()
+}
@@ -1,14 +1,126 @@
-use super::{minutes_ago, tags};
+use super::{fetch_ids, minutes_ago, tags};
+use crate::context::{EventParts, RunContext, User};
 use crate::error::Error;
-use crate::globals::{EventParts, Globals, User, GLOBALS};
 use crate::outcome::Outcome;
-use crate::WAIT;
 use nostr_types::{Event, EventKind, Filter, NAddr, Signer};
-use std::time::Duration;
 
-pub async fn delete_by_id() -> Result<Outcome, Error> {
+relay_test! {
+    DeleteById {
+        name: "Deletes by id",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: delete_by_id,
+    }
+
+    DeleteByAddr {
+        name: "Deletes by a-tag address",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: delete_by_addr,
+    }
+
+    DeleteByAddrOnlyDeletesOlder {
+        name: "Delete by a-tag deletes older but not newer",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: delete_by_addr_only_older,
+    }
+
+    DeleteByAddrIsBoundByTag {
+        name: "Delete by a-tag is bound by a-tag",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: delete_by_addr_bound_by_tag,
+    }
+
+    DeleteByIdOfOthers {
+        name: "Cannot delete by id of other people's events",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: delete_by_id_of_others,
+    }
+
+    DeleteByAddrOfOthers {
+        name: "Cannot delete by a-tag of other people's events",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: delete_by_addr_of_others,
+    }
+
+    ResubmissionOfDeletedById {
+        name: "Resubmission of deleted-by-id event is rejected",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: resubmission_of_delete_by_id,
+    }
+
+    ResubmissionOfOlderDeletedByAddr {
+        name: "Rejects submission of event before address is deleted",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: resubmission_of_older_delete_by_addr,
+    }
+
+    SubmissionOfNewerDeletedByAddr {
+        name: "Accepts submission of event after address is deleted",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: submission_of_newer_delete_by_addr,
+    }
+
+    DeletePropogatesToReferrers {
+        name: "Deleting an event leaves others' reactions to it",
+        nips: [9, 25],
+        required: false,
+        stage: Registered,
+        run: delete_propagates_to_referrers,
+    }
+
+    DeletingDeletionDoesNotUndelete {
+        name: "Deleting a deletion request does not undelete",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: deleting_deletion_does_not_undelete,
+    }
+
+    DeleteWithKindTag {
+        name: "Deletes by id with a k-tag",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: delete_with_kind_tag,
+    }
+
+    DeleteByIdAndAddr {
+        name: "Deletes by e-tag and a-tag in one request",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: delete_by_id_and_addr,
+    }
+
+    DeleteByIdBeforeEventArrives {
+        name: "Deletion by id applies to events received afterwards",
+        nips: [9],
+        required: false,
+        stage: Registered,
+        run: delete_by_id_before_event_arrives,
+    }
+}
+
+pub async fn delete_by_id(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // Make an event
-    let event = Globals::make_event(
+    let event = ctx.make_event(
         EventParts::Basic(
             EventKind::TextNote,
             tags(&[&["test"]]),
@@ -19,19 +131,16 @@ pub async fn delete_by_id() -> Result<Outcome, Error> {
     let event_id = event.id;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
     if !ok {
-        return Ok(Outcome::err(reason));
+        return Ok(Outcome::prerequisite_failed(reason));
     }
 
     // Make a deletion event, e-tag
-    let delete_event = Globals::make_event(
+    let delete_event = ctx.make_event(
         EventParts::Basic(
             EventKind::EventDeletion,
             tags(&[&["e", &event_id.as_hex_string()]]),
@@ -41,12 +150,9 @@ pub async fn delete_by_id() -> Result<Outcome, Error> {
     )?;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(delete_event, Duration::from_secs(WAIT))
+        .post_event(delete_event, ctx.timeouts().wait())
         .await?;
     if !ok {
         return Ok(Outcome::err(reason));
@@ -55,12 +161,9 @@ pub async fn delete_by_id() -> Result<Outcome, Error> {
     // Fetch back the original event
     let mut filter = Filter::new();
     filter.ids = vec![event_id.into()];
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -73,9 +176,9 @@ pub async fn delete_by_id() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn delete_by_addr() -> Result<Outcome, Error> {
+pub async fn delete_by_addr(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // Make an event
-    let event = Globals::make_event(
+    let event = ctx.make_event(
         EventParts::Basic(
             EventKind::LongFormContent,
             tags(&[&["d", "delete_by_addr_test"]]),
@@ -100,19 +203,16 @@ pub async fn delete_by_addr() -> Result<Outcome, Error> {
     );
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
     if !ok {
-        return Ok(Outcome::err(reason));
+        return Ok(Outcome::prerequisite_failed(reason));
     }
 
     // Make a deletion event, a-tag
-    let delete_event = Globals::make_event(
+    let delete_event = ctx.make_event(
         EventParts::Basic(
             EventKind::EventDeletion,
             tags(&[&["a", &a_tag]]),
@@ -122,12 +222,9 @@ pub async fn delete_by_addr() -> Result<Outcome, Error> {
     )?;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(delete_event, Duration::from_secs(WAIT))
+        .post_event(delete_event, ctx.timeouts().wait())
         .await?;
     if !ok {
         return Ok(Outcome::err(reason));
@@ -136,12 +233,9 @@ pub async fn delete_by_addr() -> Result<Outcome, Error> {
     // Fetch back the original event (by ID this time)
     let mut filter = Filter::new();
     filter.ids = vec![event_id.into()];
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -154,14 +248,14 @@ pub async fn delete_by_addr() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn delete_by_addr_only_older() -> Result<Outcome, Error> {
+pub async fn delete_by_addr_only_older(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // Prepare some times
     let time1 = minutes_ago(5);
     let time2 = minutes_ago(3);
     let time3 = minutes_ago(1);
 
     // Make an event, time1
-    let event1 = Globals::make_event(
+    let event1 = ctx.make_event(
         EventParts::Dated(
             EventKind::LongFormContent,
             tags(&[&["d", "delete_by_addr_only_older_test"]]),
@@ -187,19 +281,16 @@ pub async fn delete_by_addr_only_older() -> Result<Outcome, Error> {
     );
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event1, Duration::from_secs(WAIT))
+        .post_event(event1, ctx.timeouts().wait())
         .await?;
     if !ok {
-        return Ok(Outcome::err(reason));
+        return Ok(Outcome::prerequisite_failed(reason));
     }
 
     // Make an event, time3
-    let event3 = Globals::make_event(
+    let event3 = ctx.make_event(
         EventParts::Dated(
             EventKind::LongFormContent,
             tags(&[&["d", "delete_by_addr_only_older_test"]]),
@@ -211,19 +302,16 @@ pub async fn delete_by_addr_only_older() -> Result<Outcome, Error> {
     let event3_id = event3.id;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event3, Duration::from_secs(WAIT))
+        .post_event(event3, ctx.timeouts().wait())
         .await?;
     if !ok {
-        return Ok(Outcome::err(reason));
+        return Ok(Outcome::prerequisite_failed(reason));
     }
 
     // Make a deletion event, a-tag
-    let delete_event = Globals::make_event(
+    let delete_event = ctx.make_event(
         EventParts::Dated(
             EventKind::EventDeletion,
             tags(&[&["a", &a_tag]]),
@@ -234,12 +322,9 @@ pub async fn delete_by_addr_only_older() -> Result<Outcome, Error> {
     )?;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(delete_event, Duration::from_secs(WAIT))
+        .post_event(delete_event, ctx.timeouts().wait())
         .await?;
     if !ok {
         return Ok(Outcome::err(reason));
@@ -250,12 +335,9 @@ pub async fn delete_by_addr_only_older() -> Result<Outcome, Error> {
     filter.authors.push(naddr.author.into());
     filter.add_event_kind(naddr.kind);
     filter.add_tag_value('d', naddr.d);
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -273,10 +355,10 @@ pub async fn delete_by_addr_only_older() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn delete_by_addr_bound_by_tag() -> Result<Outcome, Error> {
+pub async fn delete_by_addr_bound_by_tag(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // Make 4 events, the final 3 differing by the first by just one factor
     let events: Vec<Event> = vec![
-        Globals::make_event(
+        ctx.make_event(
             EventParts::Basic(
                 EventKind::LongFormContent,
                 tags(&[&["d", "delete_by_addr_test_bound"]]),
@@ -284,7 +366,7 @@ pub async fn delete_by_addr_bound_by_tag() -> Result<Outcome, Error> {
             ),
             User::Registered1,
         )?,
-        Globals::make_event(
+        ctx.make_event(
             EventParts::Basic(
                 EventKind::LongFormContent,
                 tags(&[&["d", "delete_by_addr_test_bound"]]),
@@ -292,7 +374,7 @@ pub async fn delete_by_addr_bound_by_tag() -> Result<Outcome, Error> {
             ),
             User::Registered2, // different author
         )?,
-        Globals::make_event(
+        ctx.make_event(
             EventParts::Basic(
                 EventKind::LongFormContent,
                 tags(&[&["d", "delete_by_addr_test_bound_x"]]), // different d-tag
@@ -300,7 +382,7 @@ pub async fn delete_by_addr_bound_by_tag() -> Result<Outcome, Error> {
             ),
             User::Registered1,
         )?,
-        Globals::make_event(
+        ctx.make_event(
             EventParts::Basic(
                 EventKind::DraftLongFormContent, // different kind
                 tags(&[&["d", "delete_by_addr_test_bound"]]),
@@ -311,17 +393,13 @@ pub async fn delete_by_addr_bound_by_tag() -> Result<Outcome, Error> {
     ];
 
     // Submit all events
-    {
-        let mut lock = GLOBALS.connection.write();
-        for event in &events {
-            let (ok, reason) = lock
-                .as_mut()
-                .unwrap()
-                .post_event(event.clone(), Duration::from_secs(WAIT))
-                .await?;
-            if !ok {
-                return Ok(Outcome::err(reason));
-            }
+    for event in &events {
+        let (ok, reason) = ctx
+            .connection
+            .post_event(event.clone(), ctx.timeouts().wait())
+            .await?;
+        if !ok {
+            return Ok(Outcome::prerequisite_failed(reason));
         }
     }
 
@@ -333,7 +411,7 @@ pub async fn delete_by_addr_bound_by_tag() -> Result<Outcome, Error> {
             events[0].pubkey.as_hex_string(),
             "delete_by_addr_test_bound"
         );
-        Globals::make_event(
+        ctx.make_event(
             EventParts::Basic(
                 EventKind::EventDeletion,
                 tags(&[&["a", &a_tag]]),
@@ -344,12 +422,9 @@ pub async fn delete_by_addr_bound_by_tag() -> Result<Outcome, Error> {
     };
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(delete_event, Duration::from_secs(WAIT))
+        .post_event(delete_event, ctx.timeouts().wait())
         .await?;
     if !ok {
         return Ok(Outcome::err(reason));
@@ -358,12 +433,9 @@ pub async fn delete_by_addr_bound_by_tag() -> Result<Outcome, Error> {
     // Fetch all original events by id
     let mut filter = Filter::new();
     filter.ids = events.iter().map(|e| e.id.into()).collect();
-    let events_back = GLOBALS
+    let events_back = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -386,9 +458,9 @@ pub async fn delete_by_addr_bound_by_tag() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn delete_by_id_of_others() -> Result<Outcome, Error> {
+pub async fn delete_by_id_of_others(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // Make an event
-    let event = Globals::make_event(
+    let event = ctx.make_event(
         EventParts::Basic(
             EventKind::TextNote,
             tags(&[&["test"]]),
@@ -399,19 +471,16 @@ pub async fn delete_by_id_of_others() -> Result<Outcome, Error> {
     let event_id = event.id;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
     if !ok {
-        return Ok(Outcome::err(reason));
+        return Ok(Outcome::prerequisite_failed(reason));
     }
 
     // Make a deletion event, but different user e-tag
-    let delete_event = Globals::make_event(
+    let delete_event = ctx.make_event(
         EventParts::Basic(
             EventKind::EventDeletion,
             tags(&[&["e", &event_id.as_hex_string()]]),
@@ -421,12 +490,9 @@ pub async fn delete_by_id_of_others() -> Result<Outcome, Error> {
     )?;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(delete_event, Duration::from_secs(WAIT))
+        .post_event(delete_event, ctx.timeouts().wait())
         .await?;
     if !ok {
         return Ok(Outcome::pass(Some(reason)));
@@ -439,12 +505,9 @@ pub async fn delete_by_id_of_others() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -457,9 +520,9 @@ pub async fn delete_by_id_of_others() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn delete_by_addr_of_others() -> Result<Outcome, Error> {
+pub async fn delete_by_addr_of_others(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // Make an event
-    let event = Globals::make_event(
+    let event = ctx.make_event(
         EventParts::Basic(
             EventKind::LongFormContent,
             tags(&[&["d", "delete_by_addr_of_others"]]),
@@ -484,19 +547,16 @@ pub async fn delete_by_addr_of_others() -> Result<Outcome, Error> {
     );
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
     if !ok {
-        return Ok(Outcome::err(reason));
+        return Ok(Outcome::prerequisite_failed(reason));
     }
 
     // Make a deletion event, a-tag
-    let delete_event = Globals::make_event(
+    let delete_event = ctx.make_event(
         EventParts::Basic(
             EventKind::EventDeletion,
             tags(&[&["a", &a_tag]]),
@@ -506,12 +566,9 @@ pub async fn delete_by_addr_of_others() -> Result<Outcome, Error> {
     )?;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(delete_event, Duration::from_secs(WAIT))
+        .post_event(delete_event, ctx.timeouts().wait())
         .await?;
     if !ok {
         return Ok(Outcome::pass(Some(reason)));
@@ -524,12 +581,9 @@ pub async fn delete_by_addr_of_others() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -542,9 +596,9 @@ pub async fn delete_by_addr_of_others() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn resubmission_of_delete_by_id() -> Result<Outcome, Error> {
+pub async fn resubmission_of_delete_by_id(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // Make an event
-    let event = Globals::make_event(
+    let event = ctx.make_event(
         EventParts::Basic(
             EventKind::TextNote,
             tags(&[&["test"]]),
@@ -555,19 +609,16 @@ pub async fn resubmission_of_delete_by_id() -> Result<Outcome, Error> {
     let event_id = event.id;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event.clone(), Duration::from_secs(WAIT))
+        .post_event(event.clone(), ctx.timeouts().wait())
         .await?;
     if !ok {
-        return Ok(Outcome::err(reason));
+        return Ok(Outcome::prerequisite_failed(reason));
     }
 
     // Make a deletion event, e-tag
-    let delete_event = Globals::make_event(
+    let delete_event = ctx.make_event(
         EventParts::Basic(
             EventKind::EventDeletion,
             tags(&[&["e", &event_id.as_hex_string()]]),
@@ -577,24 +628,18 @@ pub async fn resubmission_of_delete_by_id() -> Result<Outcome, Error> {
     )?;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(delete_event, Duration::from_secs(WAIT))
+        .post_event(delete_event, ctx.timeouts().wait())
         .await?;
     if !ok {
         return Ok(Outcome::err(reason));
     }
 
     // Resubmit the original event
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
     if ok {
         Ok(Outcome::fail(Some("Accepted a deleted event".to_owned())))
@@ -603,12 +648,12 @@ pub async fn resubmission_of_delete_by_id() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn resubmission_of_older_delete_by_addr() -> Result<Outcome, Error> {
+pub async fn resubmission_of_older_delete_by_addr(ctx: &mut RunContext) -> Result<Outcome, Error> {
     let time1 = minutes_ago(5);
     let time2 = minutes_ago(2);
 
     // Make an event
-    let event = Globals::make_event(
+    let event = ctx.make_event(
         EventParts::Dated(
             EventKind::LongFormContent,
             tags(&[&["d", "resubmission_of_older_delete_by_addr"]]),
@@ -633,19 +678,16 @@ pub async fn resubmission_of_older_delete_by_addr() -> Result<Outcome, Error> {
     );
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event.clone(), Duration::from_secs(WAIT))
+        .post_event(event.clone(), ctx.timeouts().wait())
         .await?;
     if !ok {
-        return Ok(Outcome::err(reason));
+        return Ok(Outcome::prerequisite_failed(reason));
     }
 
     // Make a deletion event, a-tag
-    let delete_event = Globals::make_event(
+    let delete_event = ctx.make_event(
         EventParts::Dated(
             EventKind::EventDeletion,
             tags(&[&["a", &a_tag]]),
@@ -656,24 +698,18 @@ pub async fn resubmission_of_older_delete_by_addr() -> Result<Outcome, Error> {
     )?;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(delete_event, Duration::from_secs(WAIT))
+        .post_event(delete_event, ctx.timeouts().wait())
         .await?;
     if !ok {
         return Ok(Outcome::err(reason));
     }
 
     // Resubmit the original event
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
     if ok {
         Ok(Outcome::fail(Some("Accepted a deleted event".to_owned())))
@@ -682,11 +718,11 @@ pub async fn resubmission_of_older_delete_by_addr() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn submission_of_newer_delete_by_addr() -> Result<Outcome, Error> {
+pub async fn submission_of_newer_delete_by_addr(ctx: &mut RunContext) -> Result<Outcome, Error> {
     let time1 = minutes_ago(5);
     let time2 = minutes_ago(2);
 
-    let public_key = GLOBALS.registered1.read().public_key();
+    let public_key = ctx.registered1.public_key();
 
     // Compute event group address
     let naddr = NAddr {
@@ -703,7 +739,7 @@ pub async fn submission_of_newer_delete_by_addr() -> Result<Outcome, Error> {
     );
 
     // Make a deletion event, a-tag
-    let delete_event = Globals::make_event(
+    let delete_event = ctx.make_event(
         EventParts::Dated(
             EventKind::EventDeletion,
             tags(&[&["a", &a_tag]]),
@@ -714,19 +750,16 @@ pub async fn submission_of_newer_delete_by_addr() -> Result<Outcome, Error> {
     )?;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(delete_event, Duration::from_secs(WAIT))
+        .post_event(delete_event, ctx.timeouts().wait())
         .await?;
     if !ok {
-        return Ok(Outcome::err(reason));
+        return Ok(Outcome::prerequisite_failed(reason));
     }
 
     // Make an event
-    let event = Globals::make_event(
+    let event = ctx.make_event(
         EventParts::Dated(
             EventKind::LongFormContent,
             tags(&[&["d", "submission_of_newer_delete_by_addr"]]),
@@ -737,12 +770,9 @@ pub async fn submission_of_newer_delete_by_addr() -> Result<Outcome, Error> {
     )?;
 
     // Submit it
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
     if ok {
         Ok(Outcome::pass(None))
@@ -750,3 +780,339 @@ pub async fn submission_of_newer_delete_by_addr() -> Result<Outcome, Error> {
         Ok(Outcome::fail(Some(reason)))
     }
 }
+
+pub async fn delete_propagates_to_referrers(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    // Make an event
+    let event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            tags(&[&["test"]]),
+            "I say wrong thing".to_string(),
+        ),
+        User::Registered1,
+    )?;
+    let event_id = event.id;
+
+    // Submit it
+    let (ok, reason) = ctx
+        .connection
+        .post_event(event.clone(), ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::prerequisite_failed(reason));
+    }
+
+    // Someone else reacts to it
+    let reaction = ctx.make_event(
+        EventParts::Basic(
+            EventKind::Reaction,
+            tags(&[
+                &["e", &event_id.as_hex_string()],
+                &["p", &event.pubkey.as_hex_string()],
+            ]),
+            "+".to_string(),
+        ),
+        User::Registered2,
+    )?;
+    let reaction_id = reaction.id;
+
+    // Submit it
+    let (ok, reason) = ctx
+        .connection
+        .post_event(reaction, ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::prerequisite_failed(reason));
+    }
+
+    // Make a deletion event, e-tag
+    let delete_event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::EventDeletion,
+            tags(&[&["e", &event_id.as_hex_string()]]),
+            "".to_string(),
+        ),
+        User::Registered1,
+    )?;
+
+    // Submit it
+    let (ok, reason) = ctx
+        .connection
+        .post_event(delete_event, ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::err(reason));
+    }
+
+    if !fetch_ids(ctx, vec![event_id]).await?.is_empty() {
+        return Ok(Outcome::prerequisite_failed(
+            "Deleted event did not get deleted".to_owned(),
+        ));
+    }
+
+    // NIP-09 only lets a deletion remove the deleter's own events
+    if fetch_ids(ctx, vec![reaction_id]).await?.is_empty() {
+        Ok(Outcome::fail(Some(
+            "Deleted someone else's reaction to the deleted event".to_owned(),
+        )))
+    } else {
+        Ok(Outcome::pass(None))
+    }
+}
+
+pub async fn deleting_deletion_does_not_undelete(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    // Make an event
+    let event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            tags(&[&["test"]]),
+            "I say wrong thing".to_string(),
+        ),
+        User::Registered1,
+    )?;
+    let event_id = event.id;
+
+    // Submit it
+    let (ok, reason) = ctx
+        .connection
+        .post_event(event.clone(), ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::prerequisite_failed(reason));
+    }
+
+    // Make a deletion event, e-tag
+    let delete_event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::EventDeletion,
+            tags(&[&["e", &event_id.as_hex_string()], &["k", "1"]]),
+            "".to_string(),
+        ),
+        User::Registered1,
+    )?;
+    let delete_event_id = delete_event.id;
+
+    // Submit it
+    let (ok, reason) = ctx
+        .connection
+        .post_event(delete_event, ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::err(reason));
+    }
+
+    if !fetch_ids(ctx, vec![event_id]).await?.is_empty() {
+        return Ok(Outcome::prerequisite_failed(
+            "Deleted event did not get deleted".to_owned(),
+        ));
+    }
+
+    // Make a deletion of the deletion
+    let undelete_event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::EventDeletion,
+            tags(&[&["e", &delete_event_id.as_hex_string()], &["k", "5"]]),
+            "".to_string(),
+        ),
+        User::Registered1,
+    )?;
+
+    // Submit it (the relay may refuse, which is fine)
+    let _ = ctx
+        .connection
+        .post_event(undelete_event, ctx.timeouts().wait())
+        .await?;
+
+    // The original should still be deleted, and not accepted again
+    if !fetch_ids(ctx, vec![event_id]).await?.is_empty() {
+        return Ok(Outcome::fail(Some(
+            "Deleted event is served again".to_owned(),
+        )));
+    }
+
+    let (ok, _) = ctx
+        .connection
+        .post_event(event, ctx.timeouts().wait())
+        .await?;
+    if ok && !fetch_ids(ctx, vec![event_id]).await?.is_empty() {
+        Ok(Outcome::fail(Some(
+            "Accepted the deleted event again".to_owned(),
+        )))
+    } else {
+        Ok(Outcome::pass(None))
+    }
+}
+
+pub async fn delete_with_kind_tag(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    // Make an event
+    let event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            tags(&[&["test"]]),
+            "I say wrong thing".to_string(),
+        ),
+        User::Registered1,
+    )?;
+    let event_id = event.id;
+
+    // Submit it
+    let (ok, reason) = ctx
+        .connection
+        .post_event(event, ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::prerequisite_failed(reason));
+    }
+
+    // Make a deletion event, e-tag with the k-tag NIP-09 recommends
+    let delete_event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::EventDeletion,
+            tags(&[&["e", &event_id.as_hex_string()], &["k", "1"]]),
+            "".to_string(),
+        ),
+        User::Registered1,
+    )?;
+
+    // Submit it
+    let (ok, reason) = ctx
+        .connection
+        .post_event(delete_event, ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::fail(Some(reason)));
+    }
+
+    if fetch_ids(ctx, vec![event_id]).await?.is_empty() {
+        Ok(Outcome::pass(None))
+    } else {
+        Ok(Outcome::fail(Some(
+            "Deleted event did not get deleted".to_owned(),
+        )))
+    }
+}
+
+pub async fn delete_by_id_and_addr(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    // Make an event to delete by id
+    let event1 = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            tags(&[&["test"]]),
+            "I say wrong thing".to_string(),
+        ),
+        User::Registered1,
+    )?;
+
+    // And one to delete by address
+    let event2 = ctx.make_event(
+        EventParts::Basic(
+            EventKind::LongFormContent,
+            tags(&[&["d", "delete_by_id_and_addr"]]),
+            "I say wrong thing".to_string(),
+        ),
+        User::Registered1,
+    )?;
+    let a_tag = format!(
+        "{}:{}:{}",
+        Into::<u32>::into(EventKind::LongFormContent),
+        event2.pubkey.as_hex_string(),
+        "delete_by_id_and_addr"
+    );
+
+    // Submit them
+    for event in [&event1, &event2] {
+        let (ok, reason) = ctx
+            .connection
+            .post_event(event.clone(), ctx.timeouts().wait())
+            .await?;
+        if !ok {
+            return Ok(Outcome::prerequisite_failed(reason));
+        }
+    }
+
+    // Make a deletion event with both
+    let delete_event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::EventDeletion,
+            tags(&[
+                &["e", &event1.id.as_hex_string()],
+                &["a", &a_tag],
+                &["k", "1"],
+                &["k", "30023"],
+            ]),
+            "".to_string(),
+        ),
+        User::Registered1,
+    )?;
+
+    // Submit it
+    let (ok, reason) = ctx
+        .connection
+        .post_event(delete_event, ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::fail(Some(reason)));
+    }
+
+    let remaining = fetch_ids(ctx, vec![event1.id, event2.id]).await?;
+    if remaining.contains(&event1.id) {
+        Ok(Outcome::fail(Some(
+            "Failed to delete the e-tagged event".to_owned(),
+        )))
+    } else if remaining.contains(&event2.id) {
+        Ok(Outcome::fail(Some(
+            "Failed to delete the a-tagged event".to_owned(),
+        )))
+    } else {
+        Ok(Outcome::pass(None))
+    }
+}
+
+pub async fn delete_by_id_before_event_arrives(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    // Make an event, but don't submit it yet
+    let event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            tags(&[&["test"]]),
+            "I say wrong thing".to_string(),
+        ),
+        User::Registered1,
+    )?;
+    let event_id = event.id;
+
+    // Make a deletion event, e-tag
+    let delete_event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::EventDeletion,
+            tags(&[&["e", &event_id.as_hex_string()], &["k", "1"]]),
+            "".to_string(),
+        ),
+        User::Registered1,
+    )?;
+
+    // Submit it
+    let (ok, reason) = ctx
+        .connection
+        .post_event(delete_event, ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::prerequisite_failed(reason));
+    }
+
+    // Now the event turns up
+    let (ok, reason) = ctx
+        .connection
+        .post_event(event, ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::pass(Some(reason)));
+    }
+
+    if fetch_ids(ctx, vec![event_id]).await?.is_empty() {
+        Ok(Outcome::pass(None))
+    } else {
+        Ok(Outcome::fail(Some(
+            "Served an event that was already deleted".to_owned(),
+        )))
+    }
+}
@@ -0,0 +1,220 @@
+use super::{fetch_ids, tags};
+use crate::connection::{Reason, ReasonPrefix};
+use crate::context::{EventParts, RunContext, User};
+use crate::error::Error;
+use crate::outcome::Outcome;
+use nostr_types::{Event, EventKind, Filter, Signer, Unixtime};
+
+// See also DuplicateReasonIsDuplicate, which checks the OK of a duplicate
+relay_test! {
+    DuplicateStoredOnce {
+        name: "Stores a duplicate event once",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: duplicate_stored_once,
+    }
+
+    ResubmittedReplacedEventNotRestored {
+        name: "Resubmitting a replaced event does not restore it",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: resubmitted_replaced_event_not_restored,
+    }
+
+    ResubmittedDeletedEventNotRestored {
+        name: "Resubmitting a deleted event does not restore it",
+        nips: [9],
+        required: true,
+        stage: Registered,
+        run: resubmitted_deleted_event_not_restored,
+    }
+}
+
+pub async fn duplicate_stored_once(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    // A tag of its own, to scrape for it alone
+    let marker = format!("duplicate{:08x}", rand::random::<u32>());
+    let event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            tags(&[&["t", &marker]]),
+            "This will be stored once".to_string(),
+        ),
+        User::Registered1,
+    )?;
+
+    let (ok, reason) = ctx
+        .connection
+        .post_event(event.clone(), ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::prerequisite_failed(reason));
+    }
+
+    // However the duplicate is answered (DuplicateReasonIsDuplicate judges
+    // that), only one copy should be kept
+    let _ = ctx
+        .connection
+        .post_event(event.clone(), ctx.timeouts().wait())
+        .await?;
+
+    let filter = {
+        let mut filter = Filter::new();
+        filter.add_author(ctx.registered1.public_key());
+        filter.add_tag_value('t', marker);
+        filter
+    };
+    let copies = ctx
+        .connection
+        .fetch_events(filter, ctx.timeouts().wait())
+        .await?
+        .into_events()
+        .iter()
+        .filter(|e| e.id == event.id)
+        .count();
+
+    match copies {
+        1 => Ok(Outcome::pass(None)),
+        0 => Ok(Outcome::fail(Some("Did not serve the event".to_owned()))),
+        n => Ok(Outcome::fail(Some(format!("Served {} copies", n)))),
+    }
+}
+
+pub async fn resubmitted_replaced_event_not_restored(
+    ctx: &mut RunContext,
+) -> Result<Outcome, Error> {
+    // Seconds apart, so they are newer than those of any earlier run
+    let now = Unixtime::now().0;
+    let make = |content: &str, when: i64| {
+        ctx.make_event(
+            EventParts::Dated(
+                EventKind::Other(10999),
+                vec![],
+                content.to_owned(),
+                Unixtime(when),
+            ),
+            User::Registered1,
+        )
+    };
+    let older = make("The older version", now - 2)?;
+    let newer = make("The newer version", now - 1)?;
+
+    for event in [&older, &newer] {
+        let (ok, reason) = ctx
+            .connection
+            .post_event(event.clone(), ctx.timeouts().wait())
+            .await?;
+        if !ok {
+            return Ok(Outcome::prerequisite_failed(reason));
+        }
+    }
+
+    let (ok, reason) = ctx
+        .connection
+        .post_event(older.clone(), ctx.timeouts().wait())
+        .await?;
+    let reason = Reason::parse(&reason);
+
+    let filter = {
+        let mut filter = Filter::new();
+        filter.add_author(ctx.registered1.public_key());
+        filter.add_event_kind(EventKind::Other(10999));
+        filter
+    };
+    let current = fetch(ctx, filter).await?;
+    if current.iter().any(|e| e.id == older.id) {
+        return Ok(Outcome::fail(Some(
+            "Served the replaced event as current after it was resubmitted".to_owned(),
+        )));
+    }
+    if !current.iter().any(|e| e.id == newer.id) {
+        return Ok(Outcome::fail(Some(
+            "No longer served the newer event after the older was resubmitted".to_owned(),
+        )));
+    }
+
+    // A relay that keeps replaced events (ReplacedEventsStillAvailableById)
+    // already has it, and one that doesn't should refuse it
+    let still_available = !fetch_ids(ctx, vec![older.id]).await?.is_empty();
+    match (still_available, ok) {
+        (true, true) if reason.prefix == Some(ReasonPrefix::Duplicate) => Ok(Outcome::pass(Some(
+            "Kept and called a duplicate".to_owned(),
+        ))),
+        (true, _) => Ok(Outcome::fail(Some(format!(
+            "Still serves the replaced event by id, but answered {} \"{}\"",
+            ok, reason
+        )))),
+        (false, false) => Ok(Outcome::pass(Some(reason.to_string()))),
+        (false, true) => Ok(Outcome::fail(Some(format!(
+            "Accepted the replaced event but does not serve it: {}",
+            reason
+        )))),
+    }
+}
+
+pub async fn resubmitted_deleted_event_not_restored(
+    ctx: &mut RunContext,
+) -> Result<Outcome, Error> {
+    let event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            vec![],
+            "This will be deleted, then sent again".to_string(),
+        ),
+        User::Registered1,
+    )?;
+
+    let (ok, reason) = ctx
+        .connection
+        .post_event(event.clone(), ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::prerequisite_failed(reason));
+    }
+
+    let delete_event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::EventDeletion,
+            tags(&[&["e", &event.id.as_hex_string()]]),
+            "".to_string(),
+        ),
+        User::Registered1,
+    )?;
+    let (ok, reason) = ctx
+        .connection
+        .post_event(delete_event, ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::prerequisite_failed(reason));
+    }
+    if !fetch_ids(ctx, vec![event.id]).await?.is_empty() {
+        return Ok(Outcome::prerequisite_failed(
+            "Deleted event did not get deleted".to_owned(),
+        ));
+    }
+
+    // ResubmissionOfDeletedById checks this is rejected; however it is
+    // answered, the event must stay deleted
+    let (ok, reason) = ctx
+        .connection
+        .post_event(event.clone(), ctx.timeouts().wait())
+        .await?;
+
+    if !fetch_ids(ctx, vec![event.id]).await?.is_empty() {
+        Ok(Outcome::fail(Some(format!(
+            "Served the deleted event after it was resubmitted (answered {} \"{}\")",
+            ok, reason
+        ))))
+    } else {
+        Ok(Outcome::pass(None))
+    }
+}
+
+async fn fetch(ctx: &mut RunContext, filter: Filter) -> Result<Vec<Event>, Error> {
+    Ok(ctx
+        .connection
+        .fetch_events(filter, ctx.timeouts().wait())
+        .await?
+        .into_events())
+}
@@ -1,11 +1,35 @@
+use crate::context::RunContext;
 use crate::error::Error;
-use crate::globals::GLOBALS;
 use crate::outcome::Outcome;
-use crate::WAIT;
 use nostr_types::{EventKind, Filter, Id, PrivateKey, Unixtime};
-use std::time::Duration;
 
-pub async fn supports_eose() -> Result<Outcome, Error> {
+relay_test! {
+    SupportsEose {
+        name: "Supports EOSE",
+        nips: [1],
+        required: true,
+        stage: Preauth,
+        run: supports_eose,
+    }
+
+    ClosesCompleteSubscriptionsAfterEose {
+        name: "Closes complete subscriptions after EOSE",
+        nips: [1],
+        required: false,
+        stage: Preauth,
+        run: closes_complete_subscriptions_after_eose,
+    }
+
+    KeepsOpenIncompleteSubscriptionsAfterEose {
+        name: "Keeps open incomplete subscriptions after EOSE",
+        nips: [1],
+        required: true,
+        stage: Preauth,
+        run: keeps_open_incomplete_subscriptions_after_eose,
+    }
+}
+
+pub async fn supports_eose(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // A very benign filter.
     let filter = {
         let mut filter = Filter::new();
@@ -18,12 +42,9 @@ pub async fn supports_eose() -> Result<Outcome, Error> {
         filter
     };
 
-    let fresult = GLOBALS
+    let fresult = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?;
 
     match (fresult.close_msg, fresult.post_eose_events) {
@@ -37,7 +58,9 @@ pub async fn supports_eose() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn closes_complete_subscriptions_after_eose() -> Result<Outcome, Error> {
+pub async fn closes_complete_subscriptions_after_eose(
+    ctx: &mut RunContext,
+) -> Result<Outcome, Error> {
     // A filter to fetch a single event by id (a complete subscription)
     let filter = {
         let mut filter = Filter::new();
@@ -50,12 +73,9 @@ pub async fn closes_complete_subscriptions_after_eose() -> Result<Outcome, Error
         filter
     };
 
-    let fresult = GLOBALS
+    let fresult = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?;
 
     match (fresult.close_msg, fresult.post_eose_events) {
@@ -69,7 +89,9 @@ pub async fn closes_complete_subscriptions_after_eose() -> Result<Outcome, Error
     }
 }
 
-pub async fn keeps_open_incomplete_subscriptions_after_eose() -> Result<Outcome, Error> {
+pub async fn keeps_open_incomplete_subscriptions_after_eose(
+    ctx: &mut RunContext,
+) -> Result<Outcome, Error> {
     // Fetch some events of a single author (an incomplete subscription)
     let filter = {
         let mut filter = Filter::new();
@@ -83,12 +105,9 @@ pub async fn keeps_open_incomplete_subscriptions_after_eose() -> Result<Outcome,
         filter
     };
 
-    let fresult = GLOBALS
+    let fresult = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?;
 
     match (fresult.close_msg, fresult.post_eose_events) {
@@ -1,28 +1,41 @@
 use super::tags;
-use crate::connection::Connection;
+use crate::connections::Role;
+use crate::context::{EventParts, RunContext, User};
 use crate::error::Error;
-use crate::globals::{EventParts, Globals, User, GLOBALS};
 use crate::outcome::Outcome;
-use crate::WAIT;
 use nostr_types::{EventKind, Filter, Signer};
-use std::time::Duration;
 
-pub async fn ephemeral_subscriptions_work() -> Result<Outcome, Error> {
+relay_test! {
+    EphemeralSubscriptionsWork {
+        name: "Ephemeral subscriptions work",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: ephemeral_subscriptions_work,
+    }
+
+    PersistsEphemeralEvents {
+        name: "Persists ephemeral events",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: persists_ephemeral_events,
+    }
+}
+
+pub async fn ephemeral_subscriptions_work(ctx: &mut RunContext) -> Result<Outcome, Error> {
     let filter = {
         let mut filter = Filter::new();
         filter.kinds = vec![EventKind::Ephemeral(25000)];
-        filter.add_author(GLOBALS.registered1.read().public_key());
+        filter.add_author(ctx.registered1.public_key());
         filter
     };
 
     // On global connection - subscribe to the filter and wait for EOSE and a timeout
     // but keep the subscription open
-    let fresult = GLOBALS
+    let fresult = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events_keep_open(filter, Duration::from_secs(WAIT))
+        .fetch_events_keep_open(filter, ctx.timeouts().wait())
         .await?;
     let sub_id = fresult.sub_id.unwrap();
 
@@ -34,12 +47,8 @@ pub async fn ephemeral_subscriptions_work() -> Result<Outcome, Error> {
         ))));
     }
 
-    // Create a second parallel connection to the relay for injecting events
-    let relay_url = GLOBALS.relay_url.read().to_owned();
-    let mut injector = Connection::new(relay_url, 1000).await?;
-
     // Inject an ephemeral event
-    let event = Globals::make_event(
+    let event = ctx.make_event(
         EventParts::Basic(
             EventKind::Ephemeral(25000),
             tags(&[&["test"]]),
@@ -47,9 +56,11 @@ pub async fn ephemeral_subscriptions_work() -> Result<Outcome, Error> {
         ),
         User::Registered1,
     )?;
-    let (ok, reason) = injector
-        .post_event(event.clone(), Duration::from_secs(WAIT))
-        .await?;
+
+    // Create a second parallel connection to the relay for injecting events
+    let wait = ctx.timeouts().wait();
+    let injector = ctx.connect("injector", Role::Anonymous).await?;
+    let (ok, reason) = injector.post_event(event.clone(), wait).await?;
     if !ok {
         return Ok(Outcome::fail(Some(format!(
             "Relay rejected our ephemeral event: {}",
@@ -58,12 +69,9 @@ pub async fn ephemeral_subscriptions_work() -> Result<Outcome, Error> {
     }
 
     // On global connection, collect events
-    let collected_events = GLOBALS
+    let collected_events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .collect_events(sub_id, Duration::from_secs(WAIT))
+        .collect_events(sub_id, ctx.timeouts().wait())
         .await?;
 
     if collected_events.is_empty() {
@@ -79,9 +87,9 @@ pub async fn ephemeral_subscriptions_work() -> Result<Outcome, Error> {
     Ok(Outcome::pass(None))
 }
 
-pub async fn persists_ephemeral_events() -> Result<Outcome, Error> {
+pub async fn persists_ephemeral_events(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // Inject an ephemeral event
-    let event = Globals::make_event(
+    let event = ctx.make_event(
         EventParts::Basic(
             EventKind::Ephemeral(25001),
             tags(&[&["test"]]),
@@ -89,12 +97,9 @@ pub async fn persists_ephemeral_events() -> Result<Outcome, Error> {
         ),
         User::Registered1,
     )?;
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event.clone(), Duration::from_secs(WAIT))
+        .post_event(event.clone(), ctx.timeouts().wait())
         .await?;
     if !ok {
         return Ok(Outcome::fail(Some(format!(
@@ -107,16 +112,13 @@ pub async fn persists_ephemeral_events() -> Result<Outcome, Error> {
     let filter = {
         let mut filter = Filter::new();
         filter.kinds = vec![EventKind::Ephemeral(25001)];
-        filter.add_author(GLOBALS.registered1.read().public_key());
+        filter.add_author(ctx.registered1.public_key());
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
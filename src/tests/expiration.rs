@@ -0,0 +1,164 @@
+use super::{fetch_ids, minutes_ago, tags};
+use crate::context::{EventParts, RunContext, User};
+use crate::error::Error;
+use crate::outcome::Outcome;
+use nostr_types::{Event, EventKind, Filter, Unixtime};
+use std::time::Duration;
+
+/// How long the expiring events last. Long enough to read them back before
+/// they go, short enough not to hold up the run.
+const LIFETIME_SECS: i64 = 2;
+
+relay_test! {
+    ExpiredEventRejectedOrHidden {
+        name: "Already-expired events are rejected or not served",
+        nips: [40],
+        required: false,
+        stage: Registered,
+        run: expired_event_rejected_or_hidden,
+    }
+
+    ExpiringEventServedBeforeExpiry {
+        name: "Expiring events are served before they expire",
+        nips: [40],
+        required: false,
+        stage: Registered,
+        run: expiring_event_served_before_expiry,
+    }
+
+    ExpiredEventNotServedById {
+        name: "Expired events are not served by id",
+        nips: [40],
+        required: false,
+        stage: Registered,
+        run: expired_event_not_served_by_id,
+    }
+
+    ExpiredEventNotServedByScrape {
+        name: "Expired events are not served by scrape",
+        nips: [40],
+        required: false,
+        stage: Registered,
+        run: expired_event_not_served_by_scrape,
+    }
+}
+
+pub async fn expired_event_rejected_or_hidden(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    // Written an hour ago, expired half an hour ago
+    let expiration = minutes_ago(30).0.to_string();
+    let event = ctx.make_event(
+        EventParts::Dated(
+            EventKind::TextNote,
+            tags(&[&["expiration", &expiration]]),
+            "This event has already expired".to_string(),
+            minutes_ago(60),
+        ),
+        User::Registered1,
+    )?;
+
+    let (ok, reason) = ctx
+        .connection
+        .post_event(event.clone(), ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::pass(Some(reason)));
+    }
+
+    if !fetch_ids(ctx, vec![event.id]).await?.is_empty() {
+        Ok(Outcome::fail(Some(
+            "Accepted and served an expired event".to_owned(),
+        )))
+    } else {
+        Ok(Outcome::pass(None))
+    }
+}
+
+pub async fn expiring_event_served_before_expiry(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let event = match post_expiring_event(ctx).await? {
+        Ok(event) => event,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    if !fetch_ids(ctx, vec![event.id]).await?.is_empty() {
+        Ok(Outcome::pass(None))
+    } else {
+        Ok(Outcome::fail(Some(
+            "Did not serve the event before it expired".to_owned(),
+        )))
+    }
+}
+
+pub async fn expired_event_not_served_by_id(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let event = match post_expiring_event(ctx).await? {
+        Ok(event) => event,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    wait_for_expiry().await;
+
+    if !fetch_ids(ctx, vec![event.id]).await?.is_empty() {
+        Ok(Outcome::fail(Some(
+            "Served the event by id after it expired".to_owned(),
+        )))
+    } else {
+        Ok(Outcome::pass(None))
+    }
+}
+
+pub async fn expired_event_not_served_by_scrape(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let event = match post_expiring_event(ctx).await? {
+        Ok(event) => event,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    wait_for_expiry().await;
+
+    // Everything since the event was made, which should not include it
+    let filter = {
+        let mut filter = Filter::new();
+        filter.since = Some(event.created_at);
+        filter
+    };
+    let events = ctx
+        .connection
+        .fetch_events(filter, ctx.timeouts().wait())
+        .await?
+        .into_events();
+
+    if events.iter().any(|e| e.id == event.id) {
+        Ok(Outcome::fail(Some(
+            "Served the event by scrape after it expired".to_owned(),
+        )))
+    } else {
+        Ok(Outcome::pass(None))
+    }
+}
+
+// Post an event that expires LIFETIME_SECS from now. If the relay rejects
+// it, the outcome to report.
+async fn post_expiring_event(ctx: &mut RunContext) -> Result<Result<Event, Outcome>, Error> {
+    let expiration = (Unixtime::now().0 + LIFETIME_SECS).to_string();
+    let event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            tags(&[&["expiration", &expiration]]),
+            "This event expires soon".to_string(),
+        ),
+        User::Registered1,
+    )?;
+
+    let (ok, reason) = ctx
+        .connection
+        .post_event(event.clone(), ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Err(Outcome::prerequisite_failed(reason)));
+    }
+
+    Ok(Ok(event))
+}
+
+// Sleep until the events from `post_expiring_event` have expired
+async fn wait_for_expiry() {
+    tokio::time::sleep(Duration::from_secs(LIFETIME_SECS as u64 + 1)).await;
+}
@@ -1,14 +1,30 @@
 use super::tags;
+use crate::context::{EventParts, RunContext, User};
 use crate::error::Error;
-use crate::globals::{EventParts, Globals, User, GLOBALS};
 use crate::outcome::Outcome;
-use crate::WAIT;
 use nostr_types::{EventKind, Filter, Signer, Unixtime};
-use std::time::Duration;
 
-pub async fn since_until_are_inclusive() -> Result<Outcome, Error> {
+relay_test! {
+    SinceUntilAreInclusive {
+        name: "Since and until filters are inclusive",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: since_until_are_inclusive,
+    }
+
+    LimitZero {
+        name: "Limit zero works",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: limit_zero,
+    }
+}
+
+pub async fn since_until_are_inclusive(ctx: &mut RunContext) -> Result<Outcome, Error> {
     let time = Unixtime::now();
-    let event = Globals::make_event(
+    let event = ctx.make_event(
         EventParts::Dated(
             EventKind::JobRequest(5000),
             tags(&[&["test"]]),
@@ -18,19 +34,16 @@ pub async fn since_until_are_inclusive() -> Result<Outcome, Error> {
         User::Registered1,
     )?;
 
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
 
     if !ok {
-        return Ok(Outcome::err(reason));
+        return Ok(Outcome::prerequisite_failed(reason));
     }
 
-    let registered_public_key = GLOBALS.registered1.read().public_key();
+    let registered_public_key = ctx.registered1.public_key();
 
     let base_filter = {
         let mut filter = Filter::new();
@@ -43,21 +56,15 @@ pub async fn since_until_are_inclusive() -> Result<Outcome, Error> {
     let mut since_filter = base_filter.clone();
     since_filter.since = Some(time);
 
-    let until_events = GLOBALS
+    let until_events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(until_filter, Duration::from_secs(WAIT))
+        .fetch_events(until_filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
-    let since_events = GLOBALS
+    let since_events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(since_filter, Duration::from_secs(WAIT))
+        .fetch_events(since_filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -75,8 +82,8 @@ pub async fn since_until_are_inclusive() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn limit_zero() -> Result<Outcome, Error> {
-    let registered_public_key = GLOBALS.registered1.read().public_key();
+pub async fn limit_zero(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let registered_public_key = ctx.registered1.public_key();
 
     let filter = {
         let mut filter = Filter::new();
@@ -85,12 +92,9 @@ pub async fn limit_zero() -> Result<Outcome, Error> {
         filter
     };
 
-    let fetch_result = GLOBALS
+    let fetch_result = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?;
 
     if !fetch_result.pre_eose_events.is_empty() {
@@ -1,21 +1,121 @@
+use super::limits::limit;
 use super::maybe_submit_event_group_a;
+use crate::context::RunContext;
 use crate::error::Error;
-use crate::globals::GLOBALS;
 use crate::outcome::Outcome;
-use crate::WAIT;
 use nostr_types::{Event, EventKind, Filter, Id, PublicKeyHex, Signer, Unixtime};
-use std::time::Duration;
 
-pub async fn newest_to_oldest() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+relay_test! {
+    EventsOrderedFromNewestToOldest {
+        name: "Events are ordered from newest to oldest",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: newest_to_oldest,
+    }
+
+    NewestEventsWhenLimited {
+        name: "Newest events are returned when filter is limited",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: newest_events_when_limited,
+    }
+
+    FindById {
+        name: "Finds by id",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: find_by_id,
+    }
+
+    FindByPubkeyAndKind {
+        name: "Finds by pubkey and kind",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: find_by_pubkey_and_kind,
+    }
+
+    FindByPubkeyAndTags {
+        name: "Finds by pubkey and tags",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: find_by_pubkey_and_tags,
+    }
+
+    FindByKindAndTags {
+        name: "Finds by kind and tags",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: find_by_kind_and_tags,
+    }
+
+    FindByTags {
+        name: "Finds by tags",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: find_by_tags,
+    }
+
+    FindByMultipleTags {
+        name: "Finds by multiple tags",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: find_by_multiple_tags,
+    }
+
+    FindByPubkey {
+        name: "Finds by pubkey",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: find_by_pubkey,
+    }
+
+    FindByScrape {
+        name: "Finds by scrape",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: find_by_scrape,
+    }
+
+    FindByMultipleFilters {
+        name: "Finds by multiple filters",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: find_by_multiple_filters,
+    }
+
+    MultipleFiltersDeduplicated {
+        name: "Events matching several filters are sent once",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: multiple_filters_deduplicated,
+    }
+
+    LimitIsPerFilter {
+        name: "Limit applies to each filter separately",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: limit_is_per_filter,
+    }
+}
+
+pub async fn newest_to_oldest(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
     // Collect all the Ids from Event Group A
-    let ids: Vec<Id> = GLOBALS
-        .event_group_a
-        .read()
-        .iter()
-        .map(|rm| rm.0.id)
-        .collect();
+    let ids: Vec<Id> = ctx.event_group_a.iter().map(|rm| rm.0.id).collect();
 
     // Filter to read them all back
     let filter = {
@@ -24,12 +124,9 @@ pub async fn newest_to_oldest() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -50,10 +147,10 @@ pub async fn newest_to_oldest() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn newest_events_when_limited() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn newest_events_when_limited(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
-    let registered_public_key = GLOBALS.registered1.read().public_key();
+    let registered_public_key = ctx.registered1.public_key();
 
     let filter = {
         let mut filter = Filter::new();
@@ -65,29 +162,14 @@ pub async fn newest_events_when_limited() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
-    let limit_test_first_id = GLOBALS
-        .event_group_a
-        .read()
-        .get("limit_test_first")
-        .unwrap()
-        .0
-        .id;
-    let limit_test_second_id = GLOBALS
-        .event_group_a
-        .read()
-        .get("limit_test_second")
-        .unwrap()
-        .0
-        .id;
+    let limit_test_first_id = ctx.event_group_a.get("limit_test_first").unwrap().0.id;
+    let limit_test_second_id = ctx.event_group_a.get("limit_test_second").unwrap().0.id;
 
     if events.len() != 2 {
         Ok(Outcome::fail(Some(format!(
@@ -101,13 +183,12 @@ pub async fn newest_events_when_limited() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn find_by_id() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn find_by_id(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
     // Collect all the Ids from Event Group A that are findable
-    let ids: Vec<Id> = GLOBALS
+    let ids: Vec<Id> = ctx
         .event_group_a
-        .read()
         .iter()
         .filter(|v| v.1)
         .map(|rm| rm.0.id)
@@ -121,14 +202,14 @@ pub async fn find_by_id() -> Result<Outcome, Error> {
         filter
     };
 
-    find(filter, Some(num)).await
+    find(ctx, vec![filter], Some(num)).await
 }
 
-pub async fn find_by_pubkey_and_kind() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn find_by_pubkey_and_kind(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
-    let registered_public_key = GLOBALS.registered1.read().public_key();
-    let stranger_public_key = GLOBALS.stranger.read().public_key();
+    let registered_public_key = ctx.registered1.public_key();
+    let stranger_public_key = ctx.stranger.public_key();
 
     let filter = {
         let mut filter = Filter::new();
@@ -137,13 +218,13 @@ pub async fn find_by_pubkey_and_kind() -> Result<Outcome, Error> {
         filter
     };
 
-    find(filter, None).await
+    find(ctx, vec![filter], None).await
 }
 
-pub async fn find_by_pubkey_and_tags() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn find_by_pubkey_and_tags(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
-    let registered_public_key = GLOBALS.registered1.read().public_key();
+    let registered_public_key = ctx.registered1.public_key();
     let filter = {
         let mut filter = Filter::new();
         filter.add_author(registered_public_key);
@@ -152,11 +233,11 @@ pub async fn find_by_pubkey_and_tags() -> Result<Outcome, Error> {
         filter
     };
 
-    find(filter, None).await
+    find(ctx, vec![filter], None).await
 }
 
-pub async fn find_by_kind_and_tags() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn find_by_kind_and_tags(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
     let filter = {
         let mut filter = Filter::new();
@@ -169,11 +250,11 @@ pub async fn find_by_kind_and_tags() -> Result<Outcome, Error> {
         filter
     };
 
-    find(filter, None).await
+    find(ctx, vec![filter], None).await
 }
 
-pub async fn find_by_tags() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn find_by_tags(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
     let filter = {
         let mut filter = Filter::new();
@@ -181,13 +262,13 @@ pub async fn find_by_tags() -> Result<Outcome, Error> {
         filter
     };
 
-    find(filter, None).await
+    find(ctx, vec![filter], None).await
 }
 
-pub async fn find_by_multiple_tags() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn find_by_multiple_tags(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
-    let registered_public_key = GLOBALS.registered1.read().public_key();
+    let registered_public_key = ctx.registered1.public_key();
 
     let filter = {
         let mut filter = Filter::new();
@@ -199,13 +280,13 @@ pub async fn find_by_multiple_tags() -> Result<Outcome, Error> {
         filter
     };
 
-    find(filter, None).await
+    find(ctx, vec![filter], None).await
 }
 
-pub async fn find_by_pubkey() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn find_by_pubkey(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
-    let registered_public_key = GLOBALS.registered1.read().public_key();
+    let registered_public_key = ctx.registered1.public_key();
 
     let filter = {
         let mut filter = Filter::new();
@@ -213,50 +294,172 @@ pub async fn find_by_pubkey() -> Result<Outcome, Error> {
         filter
     };
 
-    find(filter, None).await
+    find(ctx, vec![filter], None).await
 }
 
-pub async fn find_by_scrape() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn find_by_scrape(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
     let filter = Filter::new();
 
-    find(filter, None).await
+    find(ctx, vec![filter], None).await
 }
 
-async fn find(filter: Filter, num_matches_expected: Option<usize>) -> Result<Outcome, Error> {
-    let findable: Vec<Event> = GLOBALS
+pub async fn find_by_multiple_filters(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+
+    let registered_public_key = ctx.registered1.public_key();
+
+    let filters = {
+        let mut tagged = Filter::new();
+        tagged.add_author(registered_public_key);
+        tagged.add_tag_value('t', "a".to_string());
+
+        let mut metadata = Filter::new();
+        metadata.add_author(registered_public_key);
+        metadata.add_event_kind(EventKind::Metadata);
+
+        let mut approved = Filter::new();
+        approved.add_event_kind(EventKind::Other(9999));
+        approved.add_tag_value('n', "approved".to_string());
+
+        vec![tagged, metadata, approved]
+    };
+    if let Some(outcome) = too_many_filters(ctx, filters.len()) {
+        return Ok(outcome);
+    }
+
+    find(ctx, filters, None).await
+}
+
+pub async fn multiple_filters_deduplicated(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+
+    let registered_public_key = ctx.registered1.public_key();
+
+    // The four tagged text notes match both
+    let filters = {
+        let mut notes = Filter::new();
+        notes.add_author(registered_public_key);
+        notes.add_event_kind(EventKind::TextNote);
+
+        let mut tagged = Filter::new();
+        tagged.add_author(registered_public_key);
+        tagged.add_tag_value('t', "a".to_string());
+        tagged.add_tag_value('t', "b".to_string());
+
+        vec![notes, tagged]
+    };
+    if let Some(outcome) = too_many_filters(ctx, filters.len()) {
+        return Ok(outcome);
+    }
+
+    find(ctx, filters, Some(4)).await
+}
+
+pub async fn limit_is_per_filter(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+
+    let registered_public_key = ctx.registered1.public_key();
+
+    let filters: Vec<Filter> = ["a", "b"]
+        .iter()
+        .map(|t| {
+            let mut filter = Filter::new();
+            filter.add_author(registered_public_key);
+            filter.add_tag_value('t', t.to_string());
+            filter.limit = Some(1);
+            filter
+        })
+        .collect();
+    if let Some(outcome) = too_many_filters(ctx, filters.len()) {
+        return Ok(outcome);
+    }
+
+    let fresult = ctx
+        .connection
+        .fetch_events_multi(filters, ctx.timeouts().wait())
+        .await?;
+    if let Some(msg) = fresult.close_msg {
+        return Ok(Outcome::fail(Some(msg)));
+    }
+    let events = fresult.into_events();
+
+    // The newest event tagged "a", and the newest tagged "b"
+    let limit_test_first_id = ctx.event_group_a.get("limit_test_first").unwrap().0.id;
+    let limit_test_second_id = ctx.event_group_a.get("limit_test_second").unwrap().0.id;
+
+    if events.len() != 2 {
+        Ok(Outcome::fail(Some(format!(
+            "Got {} events, expected 1 for each filter",
+            events.len()
+        ))))
+    } else if !events.iter().any(|e| e.id == limit_test_first_id)
+        || !events.iter().any(|e| e.id == limit_test_second_id)
+    {
+        Ok(Outcome::fail(Some(
+            "Did not get the newest event for each filter".to_owned(),
+        )))
+    } else {
+        Ok(Outcome::pass(None))
+    }
+}
+
+// If the relay advertises a max_filters below what we need, the outcome
+fn too_many_filters(ctx: &RunContext, count: usize) -> Option<Outcome> {
+    let max = limit(ctx, "max_filters").ok()?;
+    if max < count {
+        Some(Outcome::skipped(format!(
+            "Needs {} filters, but max_filters is {}",
+            count, max
+        )))
+    } else {
+        None
+    }
+}
+
+// Fetch with the filters (in one REQ) and check we get exactly those events
+// of Event Group A that match any of them
+async fn find(
+    ctx: &mut RunContext,
+    filters: Vec<Filter>,
+    num_matches_expected: Option<usize>,
+) -> Result<Outcome, Error> {
+    let matches_any = |event: &Event| filters.iter().any(|f| f.event_matches(event));
+
+    let findable: Vec<Event> = ctx
         .event_group_a
-        .read()
         .iter()
         .filter(|v| v.1)
         .map(|v| v.0.clone())
         .collect();
 
-    let fresult = GLOBALS
+    let fresult = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter.clone(), Duration::from_secs(WAIT))
+        .fetch_events_multi(filters.clone(), ctx.timeouts().wait())
         .await?;
     let maybe_error = fresult.close_msg.clone();
     let fetched = fresult.into_events();
 
-    // Verify all fetched events match the filter
-    for event in fetched.iter() {
-        if !filter.event_matches(event) {
+    // Verify all fetched events match the filter, and are not repeated
+    for (i, event) in fetched.iter().enumerate() {
+        if !matches_any(event) {
             return Ok(Outcome::fail(Some(
                 "Returned an event that does not match the filter".to_owned(),
             )));
         }
+        if fetched[..i].iter().any(|e| e.id == event.id) {
+            return Ok(Outcome::fail(Some(
+                "Returned the same event more than once".to_owned(),
+            )));
+        }
     }
 
     // Verify all findable events that also match the filter were fetched,
     // and count the matches as we go
     let mut matches: usize = 0;
     for findable_event in findable {
-        if filter.event_matches(&findable_event) {
+        if matches_any(&findable_event) {
             if !fetched.iter().any(|e| e.id == findable_event.id) {
                 if let Some(e) = maybe_error {
                     return Ok(Outcome::fail(Some(format!(
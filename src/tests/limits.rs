@@ -0,0 +1,745 @@
+use crate::connection::{Connection, FetchResult, Reason};
+use crate::connections::Role;
+use crate::context::{leading_zero_bits, make_event, EventParts, RunContext, User};
+use crate::error::Error;
+use crate::outcome::Outcome;
+use nostr_types::{
+    ClientMessage, Event, EventKind, Filter, Id, RelayMessage, Signer, SubscriptionId, Tag,
+    Unixtime,
+};
+use serde_json::Value;
+use std::time::Duration;
+
+// These read the `limitation` of the NIP-11 document stored by Nip11Provided,
+// and probe each advertised limit from just within and just beyond it
+relay_test! {
+    LimitMaxMessageLength {
+        name: "Honours advertised max_message_length",
+        nips: [11],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: max_message_length,
+    }
+
+    LimitMaxSubscriptions {
+        name: "Honours advertised max_subscriptions",
+        nips: [11],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: max_subscriptions,
+    }
+
+    LimitMaxFilters {
+        name: "Honours advertised max_filters",
+        nips: [11],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: max_filters,
+    }
+
+    LimitMaxLimit {
+        name: "Honours advertised max_limit",
+        nips: [11],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: max_limit,
+    }
+
+    LimitMaxSubidLength {
+        name: "Honours advertised max_subid_length",
+        nips: [11],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: max_subid_length,
+    }
+
+    LimitMaxEventTags {
+        name: "Honours advertised max_event_tags",
+        nips: [11],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: max_event_tags,
+    }
+
+    LimitMaxContentLength {
+        name: "Honours advertised max_content_length",
+        nips: [11],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: max_content_length,
+    }
+
+    LimitMinPowDifficulty {
+        name: "Honours advertised min_pow_difficulty",
+        nips: [11, 13],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: min_pow_difficulty,
+    }
+
+    LimitAuthRequired {
+        name: "Honours advertised auth_required",
+        nips: [11, 42],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: auth_required,
+    }
+
+    LimitRestrictedWrites {
+        name: "Honours advertised restricted_writes",
+        nips: [11],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: restricted_writes,
+    }
+
+    LimitCreatedAtLowerLimit {
+        name: "Honours advertised created_at_lower_limit",
+        nips: [11],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: created_at_lower_limit,
+    }
+
+    LimitCreatedAtUpperLimit {
+        name: "Honours advertised created_at_upper_limit",
+        nips: [11],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: created_at_upper_limit,
+    }
+}
+
+/// The highest proof of work we are prepared to mine
+const MAX_POW_TO_MINE: u32 = 20;
+
+/// The most events we are prepared to seed to probe max_limit
+const MAX_LIMIT_TO_SEED: usize = 1000;
+
+// What became of a probe
+enum Probe {
+    Accepted,
+    Rejected(String),
+    Dropped(String),
+    NoAnswer,
+}
+
+impl Probe {
+    fn from_post(result: Result<(bool, String), Error>) -> Probe {
+        match result {
+            Ok((true, _)) => Probe::Accepted,
+            Ok((false, reason)) => Probe::Rejected(reason),
+            Err(Error::TimedOut) => Probe::NoAnswer,
+            Err(e) => Probe::Dropped(format!("{}", e)),
+        }
+    }
+
+    fn from_fetch(result: Result<FetchResult, Error>) -> Probe {
+        match result {
+            Ok(FetchResult {
+                close_msg: Some(msg),
+                ..
+            }) => Probe::Rejected(msg),
+            Ok(FetchResult {
+                post_eose_events: Some(_),
+                ..
+            }) => Probe::Accepted,
+            Ok(_) | Err(Error::TimedOut) => Probe::NoAnswer,
+            Err(e) => Probe::Dropped(format!("{}", e)),
+        }
+    }
+}
+
+pub async fn max_message_length(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let max = match limit(ctx, "max_message_length") {
+        Ok(max) => max,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    // Pad the message out with a tag, so max_content_length doesn't get in the way
+    let make = |padding: usize| {
+        make_event(
+            EventParts::Basic(
+                EventKind::TextNote,
+                vec![Tag::new(&["padding", &"a".repeat(padding)])],
+                "".to_owned(),
+            ),
+            &ctx.registered1,
+        )
+    };
+    let base = wire_length(&make(0)?)?;
+    if max <= base {
+        return Ok(Outcome::skipped(format!(
+            "max_message_length of {} is too small to probe",
+            max
+        )));
+    }
+    let within = make(max - base)?;
+    let beyond = make(max - base + 1)?;
+
+    let wait = ctx.timeouts().wait();
+    let side = ctx.connect("side", Role::As(User::Registered1)).await?;
+    let within = Probe::from_post(side.post_event(within, wait).await);
+    let beyond = Probe::from_post(side.post_event(beyond, wait).await);
+
+    Ok(judge("a message", "max_message_length", within, beyond))
+}
+
+pub async fn max_subscriptions(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let max = match limit(ctx, "max_subscriptions") {
+        Ok(max) => max,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    let wait = ctx.timeouts().wait();
+    let side = ctx.connect("side", Role::As(User::Registered1)).await?;
+    let result = max_subscriptions_inner(side, wait, max).await;
+    let (within, beyond) = result?;
+
+    Ok(judge("a subscription", "max_subscriptions", within, beyond))
+}
+
+async fn max_subscriptions_inner(
+    side: &mut Connection,
+    wait: Duration,
+    max: usize,
+) -> Result<(Probe, Probe), Error> {
+    // Subscriptions that stay open, as they want future events
+    let wire = |sub_id: &SubscriptionId| {
+        serde_json::json!(["REQ", sub_id.0, { "kinds": [1], "limit": 0 }]).to_string()
+    };
+
+    let within_ids: Vec<SubscriptionId> = (0..max).map(|_| side.fresh_sub_id()).collect();
+    for sub_id in within_ids.iter() {
+        side.send_raw_message(wire(sub_id)).await?;
+    }
+    let within = await_subscriptions(side, within_ids, wait).await;
+
+    let beyond_id = side.fresh_sub_id();
+    side.send_raw_message(wire(&beyond_id)).await?;
+    let beyond = await_subscriptions(side, vec![beyond_id], wait).await;
+
+    Ok((within, beyond))
+}
+
+pub async fn max_filters(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let max = match limit(ctx, "max_filters") {
+        Ok(max) => max,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    let wait = ctx.timeouts().wait();
+    let side = ctx.connect("side", Role::As(User::Registered1)).await?;
+    let within = Probe::from_fetch(req_with_filters(side, wait, max).await);
+    let beyond = Probe::from_fetch(req_with_filters(side, wait, max + 1).await);
+
+    Ok(judge("a REQ", "max_filters", within, beyond))
+}
+
+pub async fn max_limit(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let max = match limit(ctx, "max_limit") {
+        Ok(max) => max,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    if max >= MAX_LIMIT_TO_SEED {
+        return Ok(Outcome::skipped(format!(
+            "max_limit of {} is too large to seed",
+            max
+        )));
+    }
+
+    // Seed more matching events than the limit, so a relay that ignores it
+    // has more to return
+    let marker = format!("maxlimit{:08x}", rand::random::<u32>());
+    let seeds = (0..=max)
+        .map(|i| {
+            ctx.make_event(
+                EventParts::Basic(
+                    EventKind::TextNote,
+                    vec![Tag::new(&["t", &marker])],
+                    format!("Seed {}", i),
+                ),
+                User::Registered1,
+            )
+        })
+        .collect::<Result<Vec<Event>, Error>>()?;
+
+    let wait = ctx.timeouts().wait();
+    let side = ctx.connect("side", Role::As(User::Registered1)).await?;
+    for seed in seeds {
+        let (ok, reason) = side.post_event(seed, wait).await?;
+        if !ok {
+            return Ok(Outcome::prerequisite_failed(reason));
+        }
+    }
+
+    let filter = |limit: usize| {
+        let mut filter = Filter::new();
+        filter.add_event_kind(EventKind::TextNote);
+        filter.add_tag_value('t', marker.clone());
+        filter.limit = Some(limit);
+        filter
+    };
+    let within = side.fetch_events(filter(max), wait).await;
+    let beyond = side.fetch_events(filter(max + 1), wait).await;
+
+    if let Probe::Rejected(reason) = Probe::from_fetch(within) {
+        return Ok(Outcome::fail(Some(format!(
+            "Closed a REQ within max_limit: {}",
+            reason
+        ))));
+    }
+
+    // A relay may close the REQ, or just cut the results down to size
+    match beyond {
+        Ok(result) if result.close_msg.is_none() && result.post_eose_events.is_some() => {
+            let found = result.into_events().len();
+            if found <= max {
+                Ok(Outcome::pass(Some(format!("Returned {} events", found))))
+            } else {
+                Ok(Outcome::fail(Some(format!(
+                    "Returned {} events, more than max_limit",
+                    found
+                ))))
+            }
+        }
+        other => Ok(judge(
+            "a REQ",
+            "max_limit",
+            Probe::Accepted,
+            Probe::from_fetch(other),
+        )),
+    }
+}
+
+pub async fn max_subid_length(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let max = match limit(ctx, "max_subid_length") {
+        Ok(max) => max,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    let wait = ctx.timeouts().wait();
+    let side = ctx.connect("side", Role::As(User::Registered1)).await?;
+    let within = Probe::from_fetch(req_with_sub_id(side, wait, "s".repeat(max)).await);
+    let beyond = Probe::from_fetch(req_with_sub_id(side, wait, "s".repeat(max + 1)).await);
+
+    Ok(judge(
+        "a subscription id",
+        "max_subid_length",
+        within,
+        beyond,
+    ))
+}
+
+pub async fn max_event_tags(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let max = match limit(ctx, "max_event_tags") {
+        Ok(max) => max,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    let make = |count: usize| {
+        let tags: Vec<Tag> = (0..count)
+            .map(|i| Tag::new(&["t", &format!("tag{}", i)]))
+            .collect();
+        make_event(
+            EventParts::Basic(EventKind::TextNote, tags, "Many tags".to_owned()),
+            &ctx.registered1,
+        )
+    };
+    let within = make(max)?;
+    let beyond = make(max + 1)?;
+
+    let wait = ctx.timeouts().wait();
+    let side = ctx.connect("side", Role::As(User::Registered1)).await?;
+    let within = Probe::from_post(side.post_event(within, wait).await);
+    let beyond = Probe::from_post(side.post_event(beyond, wait).await);
+
+    Ok(judge("an event", "max_event_tags", within, beyond))
+}
+
+pub async fn max_content_length(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let max = match limit(ctx, "max_content_length") {
+        Ok(max) => max,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    let make = |length: usize| {
+        make_event(
+            EventParts::Basic(EventKind::TextNote, vec![], "a".repeat(length)),
+            &ctx.registered1,
+        )
+    };
+    let within = make(max)?;
+    let beyond = make(max + 1)?;
+
+    let wait = ctx.timeouts().wait();
+    let side = ctx.connect("side", Role::As(User::Registered1)).await?;
+    let within = Probe::from_post(side.post_event(within, wait).await);
+    let beyond = Probe::from_post(side.post_event(beyond, wait).await);
+
+    Ok(judge("an event", "max_content_length", within, beyond))
+}
+
+pub async fn min_pow_difficulty(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let min = match limit(ctx, "min_pow_difficulty") {
+        Ok(min) => min,
+        Err(outcome) => return Ok(outcome),
+    };
+    let min = match u32::try_from(min) {
+        Ok(min) => min,
+        Err(_) => {
+            return Ok(Outcome::fail(Some(format!(
+                "min_pow_difficulty of {} is out of range",
+                min
+            ))))
+        }
+    };
+    if min == 0 {
+        return Ok(Outcome::skipped("No proof of work is required".to_owned()));
+    }
+    if min > MAX_POW_TO_MINE {
+        return Ok(Outcome::skipped(format!(
+            "Mining difficulty {} would take too long",
+            min
+        )));
+    }
+
+    let within = mine(ctx, min, false).await?;
+    let beyond = mine(ctx, min - 1, true).await?;
+
+    let wait = ctx.timeouts().wait();
+    let side = ctx.connect("side", Role::As(User::Registered1)).await?;
+    let within = Probe::from_post(side.post_raw_event(within.0, within.1, wait).await);
+    let beyond = Probe::from_post(side.post_raw_event(beyond.0, beyond.1, wait).await);
+
+    Ok(judge("an event", "min_pow_difficulty", within, beyond))
+}
+
+pub async fn auth_required(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let required = match flag(ctx, "auth_required") {
+        Ok(required) => required,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    let filter = {
+        let mut filter = Filter::new();
+        filter.add_event_kind(EventKind::TextNote);
+        filter.limit = Some(1);
+        filter
+    };
+
+    let wait = ctx.timeouts().wait();
+
+    // A connection that ignores any AUTH challenge
+    let anonymous = ctx.connect("anonymous", Role::Anonymous).await?;
+    let beyond = Probe::from_fetch(anonymous.fetch_events(filter.clone(), wait).await);
+
+    if !required {
+        return match beyond {
+            Probe::Accepted => Ok(Outcome::pass(None)),
+            Probe::Rejected(reason) => Ok(Outcome::fail(Some(format!(
+                "Does not advertise auth_required, but closed an unauthenticated REQ: {}",
+                reason
+            )))),
+            Probe::Dropped(e) => Ok(Outcome::fail(Some(e))),
+            Probe::NoAnswer => Ok(Outcome::fail(Some(
+                "No answer to an unauthenticated REQ".to_owned(),
+            ))),
+        };
+    }
+
+    let side = ctx.connect("side", Role::As(User::Registered1)).await?;
+    let within = Probe::from_fetch(side.fetch_events(filter, wait).await);
+
+    Ok(judge("a REQ", "auth_required", within, beyond))
+}
+
+pub async fn restricted_writes(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let restricted = match flag(ctx, "restricted_writes") {
+        Ok(restricted) => restricted,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    // A stranger, who doesn't even answer the AUTH challenge
+    let event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            vec![],
+            "Can anybody write here?".to_owned(),
+        ),
+        User::Stranger,
+    )?;
+    let wait = ctx.timeouts().wait();
+    let anonymous = ctx.connect("anonymous", Role::Anonymous).await?;
+    let beyond = Probe::from_post(anonymous.post_event(event, wait).await);
+
+    if !restricted {
+        return match beyond {
+            Probe::Accepted => Ok(Outcome::pass(None)),
+            Probe::Rejected(reason) => Ok(Outcome::fail(Some(format!(
+                "Does not advertise restricted_writes, but rejected a stranger's event: {}",
+                reason
+            )))),
+            Probe::Dropped(e) => Ok(Outcome::fail(Some(e))),
+            Probe::NoAnswer => Ok(Outcome::fail(Some(
+                "No answer to a stranger's event".to_owned(),
+            ))),
+        };
+    }
+
+    let event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            vec![],
+            "A registered user can write here".to_owned(),
+        ),
+        User::Registered1,
+    )?;
+    let side = ctx.connect("side", Role::As(User::Registered1)).await?;
+    let within = Probe::from_post(side.post_event(event, wait).await);
+
+    Ok(judge("an event", "restricted_writes", within, beyond))
+}
+
+pub async fn created_at_lower_limit(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let lower = match limit(ctx, "created_at_lower_limit") {
+        Ok(lower) => lower as i64,
+        Err(outcome) => return Ok(outcome),
+    };
+    let margin = time_margin(lower);
+
+    let now = Unixtime::now().0;
+    created_at_probe(
+        ctx,
+        "created_at_lower_limit",
+        Unixtime(now - lower + margin),
+        Unixtime(now - lower - margin),
+    )
+    .await
+}
+
+pub async fn created_at_upper_limit(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let upper = match limit(ctx, "created_at_upper_limit") {
+        Ok(upper) => upper as i64,
+        Err(outcome) => return Ok(outcome),
+    };
+    let margin = time_margin(upper);
+
+    let now = Unixtime::now().0;
+    created_at_probe(
+        ctx,
+        "created_at_upper_limit",
+        Unixtime(now + upper - margin),
+        Unixtime(now + upper + margin),
+    )
+    .await
+}
+
+async fn created_at_probe(
+    ctx: &mut RunContext,
+    name: &str,
+    within: Unixtime,
+    beyond: Unixtime,
+) -> Result<Outcome, Error> {
+    let make = |when: Unixtime| {
+        ctx.make_event(
+            EventParts::Dated(
+                EventKind::TextNote,
+                vec![],
+                format!("Dated near the {}", name),
+                when,
+            ),
+            User::Registered1,
+        )
+    };
+    let within = make(within)?;
+    let beyond = make(beyond)?;
+
+    let wait = ctx.timeouts().wait();
+    let side = ctx.connect("side", Role::As(User::Registered1)).await?;
+    let within = Probe::from_post(side.post_event(within, wait).await);
+    let beyond = Probe::from_post(side.post_event(beyond, wait).await);
+
+    Ok(judge("an event", name, within, beyond))
+}
+
+// Judge the pair of probes: the one within the limit should be accepted,
+// and the one beyond it rejected with a reason that says why
+fn judge(what: &str, limit: &str, within: Probe, beyond: Probe) -> Outcome {
+    match within {
+        Probe::Accepted => {}
+        Probe::Rejected(reason) => {
+            return Outcome::fail(Some(format!(
+                "Rejected {} within {}: {}",
+                what, limit, reason
+            )))
+        }
+        Probe::Dropped(e) => {
+            return Outcome::fail(Some(format!(
+                "Dropped the connection over {} within {}: {}",
+                what, limit, e
+            )))
+        }
+        Probe::NoAnswer => {
+            return Outcome::fail(Some(format!("No answer to {} within {}", what, limit)))
+        }
+    }
+
+    match beyond {
+        Probe::Accepted => Outcome::fail(Some(format!("Accepted {} beyond {}", what, limit))),
+        Probe::Rejected(reason) if Reason::parse(&reason).prefix.is_some() => {
+            Outcome::pass(Some(reason))
+        }
+        Probe::Rejected(reason) => Outcome::fail(Some(format!(
+            "Rejected {} beyond {} without a standard reason: {}",
+            what, limit, reason
+        ))),
+        Probe::Dropped(e) => Outcome::pass(Some(format!("Dropped the connection: {}", e))),
+        Probe::NoAnswer => Outcome::fail(Some(format!("No answer to {} beyond {}", what, limit))),
+    }
+}
+
+// The advertised limitation value, or the outcome if there isn't one
+fn limitation<'a>(ctx: &'a RunContext, key: &str) -> Result<&'a Value, Outcome> {
+    let nip11 = match ctx.nip11 {
+        Some(ref nip11) => nip11,
+        None => return Err(Outcome::skipped("NIP-11 document was not found".to_owned())),
+    };
+    match nip11.get("limitation").and_then(|l| l.get(key)) {
+        Some(value) => Ok(value),
+        None => Err(Outcome::skipped(format!(
+            "Relay does not advertise {}",
+            key
+        ))),
+    }
+}
+
+// The advertised integer limitation value, or the outcome if there isn't
+// one (or it is not an integer)
+pub fn limit(ctx: &RunContext, key: &str) -> Result<usize, Outcome> {
+    let value = limitation(ctx, key)?;
+    match value.as_u64() {
+        Some(n) => Ok(n as usize),
+        None => Err(Outcome::fail(Some(format!(
+            "{} is not an integer: {}",
+            key, value
+        )))),
+    }
+}
+
+fn flag(ctx: &RunContext, key: &str) -> Result<bool, Outcome> {
+    let value = limitation(ctx, key)?;
+    match value.as_bool() {
+        Some(b) => Ok(b),
+        None => Err(Outcome::fail(Some(format!(
+            "{} is not a boolean: {}",
+            key, value
+        )))),
+    }
+}
+
+// How far either side of a time limit to probe
+fn time_margin(limit: i64) -> i64 {
+    (limit / 4).clamp(1, 60)
+}
+
+fn wire_length(event: &Event) -> Result<usize, Error> {
+    let message = ClientMessage::Event(Box::new(event.clone()));
+    Ok(serde_json::to_string(&message)?.len())
+}
+
+async fn req_with_filters(
+    side: &mut Connection,
+    wait: Duration,
+    count: usize,
+) -> Result<FetchResult, Error> {
+    let filters: Vec<Filter> = (0..count)
+        .map(|i| {
+            let mut filter = Filter::new();
+            filter.add_event_kind(EventKind::TextNote);
+            filter.add_tag_value('t', format!("filter{}", i));
+            filter
+        })
+        .collect();
+    side.fetch_events_multi(filters, wait).await
+}
+
+async fn req_with_sub_id(
+    side: &mut Connection,
+    wait: Duration,
+    sub_id: String,
+) -> Result<FetchResult, Error> {
+    let sub_id = SubscriptionId(sub_id);
+    let mut filter = Filter::new();
+    filter.add_event_kind(EventKind::TextNote);
+    filter.limit = Some(1);
+    side.send_message(ClientMessage::Req(sub_id.clone(), filter))
+        .await?;
+    side.receive_events(sub_id, wait, true).await
+}
+
+// Wait until each subscription has had EOSE or been closed. Accepted if
+// none were closed.
+async fn await_subscriptions(
+    side: &mut Connection,
+    mut pending: Vec<SubscriptionId>,
+    timeout: Duration,
+) -> Probe {
+    while !pending.is_empty() {
+        match side.wait_for_message(timeout).await {
+            Ok(None) | Err(Error::TimedOut) => return Probe::NoAnswer,
+            Err(e) => return Probe::Dropped(format!("{}", e)),
+            Ok(Some(RelayMessage::Eose(sub))) => pending.retain(|s| *s != sub),
+            Ok(Some(RelayMessage::Closed(sub, msg))) if pending.contains(&sub) => {
+                return Probe::Rejected(msg)
+            }
+            Ok(Some(_)) => {}
+        }
+    }
+    Probe::Accepted
+}
+
+// Mine a text note with the given proof of work (NIP-13): at least that
+// many leading zero bits, or exactly that many if `exact`. The mining runs
+// off the async runtime, so it doesn't hold up the connections.
+async fn mine(ctx: &RunContext, difficulty: u32, exact: bool) -> Result<(Id, String), Error> {
+    use secp256k1::hashes::Hash;
+
+    let pubkey = ctx.registered1.public_key().as_hex_string();
+    let created_at = Unixtime::now().0.to_string();
+    let content = "Proof of work";
+
+    let serial_created_at = created_at.clone();
+    let tags = tokio::task::spawn_blocking(move || {
+        let mut nonce: u64 = 0;
+        loop {
+            let tags = format!(r#"[["nonce","{}","{}"]]"#, nonce, difficulty);
+            let serial = format!(
+                "[0,\"{}\",{},1,{},\"{}\"]",
+                pubkey, serial_created_at, tags, content
+            );
+            let hash = secp256k1::hashes::sha256::Hash::hash(serial.as_bytes());
+            let bits = leading_zero_bits(&hash.to_byte_array());
+            if bits == difficulty || (!exact && bits > difficulty) {
+                return tags;
+            }
+            nonce += 1;
+        }
+    })
+    .await?;
+
+    Ok(ctx.make_raw_event(&created_at, "1", &tags, content, User::Registered1))
+}
@@ -1,12 +1,20 @@
+use crate::context::{RunContext, User};
 use crate::error::Error;
-use crate::globals::{Globals, User, GLOBALS};
 use crate::outcome::Outcome;
-use crate::WAIT;
 use nostr_types::Unixtime;
-use std::time::Duration;
 
-pub async fn empty_tags() -> Result<Outcome, Error> {
-    let (id, raw_event) = Globals::make_raw_event(
+relay_test! {
+    AcceptsEventsWithEmptyTags {
+        name: "Accepts events with empty tags",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: empty_tags,
+    }
+}
+
+pub async fn empty_tags(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let (id, raw_event) = ctx.make_raw_event(
         &format!("{}", Unixtime::now().0),
         "1",
         "[[],[]]",
@@ -14,12 +22,9 @@ pub async fn empty_tags() -> Result<Outcome, Error> {
         User::Registered1,
     );
 
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_raw_event(id, raw_event, Duration::from_secs(WAIT))
+        .post_raw_event(id, raw_event, ctx.timeouts().wait())
         .await?;
 
     if ok {
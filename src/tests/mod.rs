@@ -1,27 +1,133 @@
+/// Define built-in tests. Each becomes a unit struct named by its id that
+/// implements [`RelayTest`] by calling `run` with the run context (and any
+/// extra arguments given). The module's tests are listed in `TESTS`.
+macro_rules! relay_test {
+    ($(
+        $id:ident {
+            name: $name:expr,
+            $(nips: [$($nip:expr),*],)?
+            required: $required:expr,
+            stage: $stage:ident,
+            $(prerequisites: [$($prereq:ident),*],)?
+            run: $run:ident $(($($arg:expr),*))?,
+        }
+    )*) => {
+        $(
+            pub struct $id;
+
+            impl $crate::test_item::RelayTest for $id {
+                fn id(&self) -> &'static str {
+                    stringify!($id)
+                }
+
+                fn name(&self) -> &'static str {
+                    $name
+                }
+
+                fn nips(&self) -> &'static [u32] {
+                    &[$($($nip),*)?]
+                }
+
+                fn required(&self) -> bool {
+                    $required
+                }
+
+                fn stage(&self) -> $crate::stage::Stage {
+                    $crate::stage::Stage::$stage
+                }
+
+                fn module(&self) -> &'static str {
+                    module_path!().rsplit("::").next().unwrap()
+                }
+
+                fn prerequisites(&self) -> &'static [&'static str] {
+                    &[$($(stringify!($prereq)),*)?]
+                }
+
+                fn run<'a>(
+                    &'a self,
+                    ctx: &'a mut $crate::context::RunContext,
+                ) -> futures_util::future::BoxFuture<
+                    'a,
+                    Result<$crate::outcome::Outcome, $crate::error::Error>,
+                > {
+                    Box::pin($run(ctx $($(, $arg)*)?))
+                }
+            }
+        )*
+
+        pub(crate) const TESTS: &[&dyn $crate::test_item::RelayTest] = &[$(&$id),*];
+    };
+}
+
 pub mod auth;
+pub mod auth_isolation;
+pub mod capacity;
+pub mod count;
 pub mod delete;
+pub mod duplicates;
 pub mod eose;
 pub mod ephemeral;
+pub mod expiration;
 pub mod filters;
 pub mod find;
 pub mod json;
+pub mod limits;
 pub mod misc_events;
 pub mod nip11;
+pub mod nip11_schema;
 pub mod public;
+pub mod reasons;
 pub mod reg;
 pub mod replaceables;
+pub mod search;
+pub mod subscriptions;
+pub mod tbd;
 pub mod time;
 
+use crate::context::{EventParts, RunContext};
 use crate::error::Error;
-use crate::globals::{EventParts, GLOBALS};
 use crate::outcome::Outcome;
-use nostr_types::{EventKind, Tag, Unixtime};
+use crate::test_item::RelayTest;
+use nostr_types::{EventKind, Filter, Id, Tag, Unixtime};
 use std::ops::Sub;
-use std::sync::atomic::Ordering;
 use std::time::Duration;
 
-pub fn tbd() -> Result<Outcome, Error> {
-    Ok(Outcome::err("NOT YET IMPLEMENTED".to_string()))
+/// The built-in tests, in the order they run within each stage
+pub(crate) fn builtin() -> impl Iterator<Item = &'static dyn RelayTest> {
+    [
+        nip11::TESTS,
+        nip11_schema::TESTS,
+        auth::TESTS,
+        eose::TESTS,
+        public::TESTS,
+        reg::TESTS,
+        reasons::TESTS,
+        auth_isolation::TESTS,
+        json::TESTS,
+        time::TESTS,
+        misc_events::TESTS,
+        find::TESTS,
+        filters::TESTS,
+        subscriptions::TESTS,
+        count::TESTS,
+        search::TESTS,
+        ephemeral::TESTS,
+        expiration::TESTS,
+        replaceables::TESTS,
+        delete::TESTS,
+        duplicates::TESTS,
+        limits::TESTS,
+        capacity::TESTS,
+        tbd::TESTS,
+    ]
+    .into_iter()
+    .flatten()
+    .copied()
+}
+
+pub async fn tbd(_ctx: &mut RunContext) -> Result<Outcome, Error> {
+    Ok(Outcome::not_implemented())
 }
 
 fn tags(intags: &[&[&str]]) -> Vec<Tag> {
@@ -32,223 +138,283 @@ fn tags(intags: &[&[&str]]) -> Vec<Tag> {
     tags
 }
 
-async fn maybe_submit_event_group_a() -> Result<(), Error> {
-    if GLOBALS.event_group_a_submitted.load(Ordering::Relaxed) {
+async fn maybe_submit_event_group_a(ctx: &mut RunContext) -> Result<(), Error> {
+    if ctx.event_group_a_submitted {
         // Already submitted
         return Ok(());
     }
 
-    if GLOBALS.event_group_a_failed.load(Ordering::Relaxed) {
+    if ctx.event_group_a_failed {
         // Already tried and it failed
         return Err(Error::PrerequisiteEventSubmissionFailed);
     }
 
-    match maybe_submit_event_group_a_inner().await {
+    match maybe_submit_event_group_a_inner(ctx).await {
         Ok(()) => {
-            GLOBALS
-                .event_group_a_submitted
-                .store(true, Ordering::Relaxed);
+            ctx.event_group_a_submitted = true;
             Ok(())
         }
         Err(e) => {
-            GLOBALS.event_group_a_failed.store(true, Ordering::Relaxed);
+            ctx.event_group_a_failed = true;
             Err(e)
         }
     }
 }
 
-async fn maybe_submit_event_group_a_inner() -> Result<(), Error> {
-    let mut lock = GLOBALS.event_group_a.write();
-
-    lock.insert(
-        "limit_test_first",
-        EventParts::Dated(
-            EventKind::TextNote,
-            tags(&[&["t", "a"]]),
-            "limit_test_first".to_owned(),
-            minutes_ago(40),
-        ),
-        true,
-    )
-    .await?;
-
-    lock.insert(
-        "limit_test_third",
-        EventParts::Dated(
-            EventKind::TextNote,
-            tags(&[&["t", "a"]]),
-            "limit_test_third".to_owned(),
-            minutes_ago(50),
-        ),
-        true,
-    )
-    .await?;
-
-    lock.insert(
-        "limit_test_second",
-        EventParts::Dated(
-            EventKind::TextNote,
-            tags(&[&["t", "b"]]),
-            "limit_test_second".to_owned(),
-            minutes_ago(45),
-        ),
-        true,
-    )
-    .await?;
-
-    lock.insert(
-        "limit_test_fourth",
-        EventParts::Dated(
-            EventKind::TextNote,
-            tags(&[&["t", "b"]]),
-            "limit_test_fourth".to_owned(),
-            minutes_ago(55),
-        ),
-        true,
-    )
-    .await?;
-
-    lock.insert(
-        "metadata_older",
-        EventParts::Dated(
-            EventKind::Metadata,
-            tags(&[]),
-            "metadata_older".to_owned(),
-            minutes_ago(60),
-        ),
-        false,
-    )
-    .await?;
-
-    lock.insert(
-        "metadata_newer",
-        EventParts::Dated(
-            EventKind::Metadata,
-            tags(&[]),
-            "metadata_newer".to_owned(),
-            minutes_ago(0),
-        ),
-        true,
-    )
-    .await?;
-
-    lock.insert(
-        "contactlist_newer",
-        EventParts::Dated(
-            EventKind::ContactList,
-            tags(&[]),
-            "contactlist_newer".to_owned(),
-            minutes_ago(10),
-        ),
-        true,
-    )
-    .await?;
-
-    lock.insert(
-        "contactlist_older",
-        EventParts::Dated(
-            EventKind::ContactList,
-            tags(&[]),
-            "contactlist_older".to_owned(),
-            minutes_ago(70),
-        ),
-        false,
-    )
-    .await?;
-
-    lock.insert(
-        "ephemeral",
-        EventParts::Dated(
-            EventKind::Ephemeral(21212),
-            tags(&[]),
-            "ephemeral".to_owned(),
-            minutes_ago(10),
-        ),
-        false,
-    )
-    .await?;
-
-    lock.insert(
-        "multipletags",
-        EventParts::Dated(
-            EventKind::Other(9999),
-            tags(&[&["k", "3036"], &["n", "approved"]]),
-            "multipletags".to_owned(),
-            minutes_ago(10),
-        ),
-        true,
-    )
-    .await?;
-
-    lock.insert(
-        "multipletags_shouldntmatch",
-        EventParts::Dated(
-            EventKind::Other(9999),
-            tags(&[&["n", "approved"]]),
-            "multipletags_shouldntmatch".to_owned(),
-            minutes_ago(10),
-        ),
-        true,
-    )
-    .await?;
-
-    lock.insert(
-        "older_param_replaceable",
-        EventParts::Dated(
-            EventKind::FollowSets,
-            tags(&[&["d", "1"]]),
-            "older_param_replaceable".to_owned(),
-            minutes_ago(120),
-        ),
-        false,
-    )
-    .await?;
-
-    lock.insert(
-        "newer_param_replaceable",
-        EventParts::Dated(
-            EventKind::FollowSets,
-            tags(&[&["d", "1"]]),
-            "newer_param_replaceable".to_owned(),
-            minutes_ago(60),
-        ),
-        true,
-    )
-    .await?;
-
-    lock.insert(
-        "older_replaceable",
-        EventParts::Dated(
-            EventKind::BookmarkList,
-            tags(&[&[
-                "e",
-                "65f07794c052916f434d2a40ad4e3c58c1c287d829b999977a7221c0ebadab0a",
-            ]]),
-            "older_replaceable".to_owned(),
-            minutes_ago(80),
-        ),
-        false,
-    )
-    .await?;
-
-    lock.insert(
-        "newer_replaceable",
-        EventParts::Dated(
-            EventKind::BookmarkList,
-            tags(&[&[
-                "e",
-                "65f07794c052916f434d2a40ad4e3c58c1c287d829b999977a7221c0ebadab0a",
-            ]]),
-            "newer_replaceable".to_owned(),
-            minutes_ago(60),
-        ),
-        false,
-    )
-    .await?;
+async fn maybe_submit_event_group_a_inner(ctx: &mut RunContext) -> Result<(), Error> {
+    let RunContext {
+        ref mut connection,
+        ref registered1,
+        ref mut event_group_a,
+        ..
+    } = *ctx;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "limit_test_first",
+            EventParts::Dated(
+                EventKind::TextNote,
+                tags(&[&["t", "a"]]),
+                "limit_test_first".to_owned(),
+                minutes_ago(40),
+            ),
+            true,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "limit_test_third",
+            EventParts::Dated(
+                EventKind::TextNote,
+                tags(&[&["t", "a"]]),
+                "limit_test_third".to_owned(),
+                minutes_ago(50),
+            ),
+            true,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "limit_test_second",
+            EventParts::Dated(
+                EventKind::TextNote,
+                tags(&[&["t", "b"]]),
+                "limit_test_second".to_owned(),
+                minutes_ago(45),
+            ),
+            true,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "limit_test_fourth",
+            EventParts::Dated(
+                EventKind::TextNote,
+                tags(&[&["t", "b"]]),
+                "limit_test_fourth".to_owned(),
+                minutes_ago(55),
+            ),
+            true,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "metadata_older",
+            EventParts::Dated(
+                EventKind::Metadata,
+                tags(&[]),
+                "metadata_older".to_owned(),
+                minutes_ago(60),
+            ),
+            false,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "metadata_newer",
+            EventParts::Dated(
+                EventKind::Metadata,
+                tags(&[]),
+                "metadata_newer".to_owned(),
+                minutes_ago(0),
+            ),
+            true,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "contactlist_newer",
+            EventParts::Dated(
+                EventKind::ContactList,
+                tags(&[]),
+                "contactlist_newer".to_owned(),
+                minutes_ago(10),
+            ),
+            true,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "contactlist_older",
+            EventParts::Dated(
+                EventKind::ContactList,
+                tags(&[]),
+                "contactlist_older".to_owned(),
+                minutes_ago(70),
+            ),
+            false,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "ephemeral",
+            EventParts::Dated(
+                EventKind::Ephemeral(21212),
+                tags(&[]),
+                "ephemeral".to_owned(),
+                minutes_ago(10),
+            ),
+            false,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "multipletags",
+            EventParts::Dated(
+                EventKind::Other(9999),
+                tags(&[&["k", "3036"], &["n", "approved"]]),
+                "multipletags".to_owned(),
+                minutes_ago(10),
+            ),
+            true,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "multipletags_shouldntmatch",
+            EventParts::Dated(
+                EventKind::Other(9999),
+                tags(&[&["n", "approved"]]),
+                "multipletags_shouldntmatch".to_owned(),
+                minutes_ago(10),
+            ),
+            true,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "older_param_replaceable",
+            EventParts::Dated(
+                EventKind::FollowSets,
+                tags(&[&["d", "1"]]),
+                "older_param_replaceable".to_owned(),
+                minutes_ago(120),
+            ),
+            false,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "newer_param_replaceable",
+            EventParts::Dated(
+                EventKind::FollowSets,
+                tags(&[&["d", "1"]]),
+                "newer_param_replaceable".to_owned(),
+                minutes_ago(60),
+            ),
+            true,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "older_replaceable",
+            EventParts::Dated(
+                EventKind::BookmarkList,
+                tags(&[&[
+                    "e",
+                    "65f07794c052916f434d2a40ad4e3c58c1c287d829b999977a7221c0ebadab0a",
+                ]]),
+                "older_replaceable".to_owned(),
+                minutes_ago(80),
+            ),
+            false,
+        )
+        .await?;
+
+    event_group_a
+        .insert(
+            connection,
+            registered1,
+            "newer_replaceable",
+            EventParts::Dated(
+                EventKind::BookmarkList,
+                tags(&[&[
+                    "e",
+                    "65f07794c052916f434d2a40ad4e3c58c1c287d829b999977a7221c0ebadab0a",
+                ]]),
+                "newer_replaceable".to_owned(),
+                minutes_ago(60),
+            ),
+            false,
+        )
+        .await?;
 
     Ok(())
 }
 
+// The ids of those events the relay serves, when asked for them by id
+async fn fetch_ids(ctx: &mut RunContext, ids: Vec<Id>) -> Result<Vec<Id>, Error> {
+    let mut filter = Filter::new();
+    filter.ids = ids;
+    let events = ctx
+        .connection
+        .fetch_events(filter, ctx.timeouts().wait())
+        .await?
+        .into_events();
+    Ok(events.iter().map(|e| e.id).collect())
+}
+
 fn minutes_ago(m: u64) -> Unixtime {
     Unixtime::now().sub(Duration::new(m * 60, 0))
 }
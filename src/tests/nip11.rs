@@ -1,18 +1,148 @@
+use crate::context::RunContext;
 use crate::error::Error;
-use crate::globals::GLOBALS;
 use crate::outcome::Outcome;
 use serde_json::Value;
 
-pub async fn nip11_provided() -> Result<Outcome, Error> {
-    let nip11 = crate::connection::fetch_nip11().await?;
+relay_test! {
+    Nip11Provided {
+        name: "NIP-11 document is provided",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        run: nip11_provided,
+    }
+
+    // These read the NIP-11 document stored by Nip11Provided
+    ClaimsSupportForNip4 {
+        name: "Claims support for NIP-04 (old DMs)",
+        nips: [11, 4],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(4),
+    }
+
+    ClaimsSupportForNip9 {
+        name: "Claims support for NIP-09 (Deletion)",
+        nips: [11, 9],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(9),
+    }
+
+    ClaimsSupportForNip11 {
+        name: "Claims support for NIP-11 (Relay Information Document)",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(11),
+    }
+
+    ClaimsSupportForNip26 {
+        name: "Claims support for NIP-26 (Delegated Event Signing)",
+        nips: [11, 26],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(26),
+    }
+
+    ClaimsSupportForNip29 {
+        name: "Claims support for NIP-29 (Relay-based Groups)",
+        nips: [11, 29],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(29),
+    }
+
+    ClaimsSupportForNip40 {
+        name: "Claims support for NIP-40 (Expiration Timestamp)",
+        nips: [11, 40],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(40),
+    }
+
+    ClaimsSupportForNip42 {
+        name: "Claims support for NIP-42 (AUTH)",
+        nips: [11, 42],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(42),
+    }
+
+    ClaimsSupportForNip45 {
+        name: "Claims support for NIP-45 (COUNT)",
+        nips: [11, 45],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(45),
+    }
+
+    ClaimsSupportForNip50 {
+        name: "Claims support for NIP-50 (SEARCH)",
+        nips: [11, 50],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(50),
+    }
+
+    ClaimsSupportForNip59 {
+        name: "Claims support for NIP-59 (Giftwrap)",
+        nips: [11, 59],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(59),
+    }
+
+    ClaimsSupportForNip65 {
+        name: "Claims support for NIP-65 (Relay Lists)",
+        nips: [11, 65],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(65),
+    }
+
+    ClaimsSupportForNip94 {
+        name: "Claims support for NIP-94 (File Metadata)",
+        nips: [11, 94],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(94),
+    }
+
+    ClaimsSupportForNip96 {
+        name: "Claims support for NIP-96 (HTTP file storage)",
+        nips: [11, 96],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: claimed_support_for_nip(96),
+    }
+}
 
-    *GLOBALS.nip11.write() = Some(nip11);
+pub async fn nip11_provided(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let (nip11, headers) =
+        crate::connection::fetch_nip11(&ctx.relay_url, ctx.timeouts().nip11()).await?;
+
+    ctx.nip11 = Some(nip11);
+    ctx.nip11_headers = Some(headers);
 
     Ok(Outcome::pass(None))
 }
 
-pub async fn claimed_support_for_nip(number: u64) -> Result<Outcome, Error> {
-    let nip11 = GLOBALS.nip11.read().clone();
+pub async fn claimed_support_for_nip(ctx: &mut RunContext, number: u64) -> Result<Outcome, Error> {
+    let nip11 = ctx.nip11.clone();
     if nip11.is_none() {
         return Ok(Outcome::fail(Some(
             "NIP-11 document was not found".to_owned(),
@@ -20,13 +150,22 @@ pub async fn claimed_support_for_nip(number: u64) -> Result<Outcome, Error> {
     }
     let nip11 = nip11.unwrap();
 
+    if claims_nip(&nip11, number) {
+        Ok(Outcome::pass(None))
+    } else {
+        Ok(Outcome::fail(None))
+    }
+}
+
+/// Whether the NIP-11 document lists the NIP in `supported_nips`
+pub fn claims_nip(nip11: &Value, number: u64) -> bool {
     if let Value::Object(map) = nip11 {
         if let Some(Value::Array(vec)) = map.get("supported_nips") {
             for valelem in vec.iter() {
                 if let Value::Number(vnum) = valelem {
                     if let Some(u) = vnum.as_u64() {
                         if u == number {
-                            return Ok(Outcome::pass(None));
+                            return true;
                         }
                     }
                 }
@@ -34,5 +173,5 @@ pub async fn claimed_support_for_nip(number: u64) -> Result<Outcome, Error> {
         }
     }
 
-    Ok(Outcome::fail(None))
+    false
 }
@@ -0,0 +1,430 @@
+use crate::context::RunContext;
+use crate::error::Error;
+use crate::outcome::Outcome;
+use serde_json::{Map, Value};
+
+// These read the NIP-11 document (and headers) stored by Nip11Provided
+relay_test! {
+    Nip11ContentType {
+        name: "NIP-11 document is served as application/nostr+json",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: content_type,
+    }
+
+    Nip11AllowsOrigin {
+        name: "NIP-11 response sends Access-Control-Allow-Origin",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: cors_header("access-control-allow-origin"),
+    }
+
+    Nip11AllowsHeaders {
+        name: "NIP-11 response sends Access-Control-Allow-Headers",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: cors_header("access-control-allow-headers"),
+    }
+
+    Nip11AllowsMethods {
+        name: "NIP-11 response sends Access-Control-Allow-Methods",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: cors_header("access-control-allow-methods"),
+    }
+
+    Nip11NameIsString {
+        name: "NIP-11 name is a string",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: field_is("name", is_string),
+    }
+
+    Nip11DescriptionIsString {
+        name: "NIP-11 description is a string",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: field_is("description", is_string),
+    }
+
+    Nip11PubkeyIsHex {
+        name: "NIP-11 pubkey is 64 lowercase hex digits",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: field_is("pubkey", is_hex_key),
+    }
+
+    Nip11SelfIsHex {
+        name: "NIP-11 self is 64 lowercase hex digits",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: field_is("self", is_hex_key),
+    }
+
+    Nip11SupportedNipsAreIntegers {
+        name: "NIP-11 supported_nips is a list of integers",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: field_is("supported_nips", is_integer_list),
+    }
+
+    Nip11SoftwareIsString {
+        name: "NIP-11 software is a string",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: field_is("software", is_string),
+    }
+
+    Nip11VersionIsString {
+        name: "NIP-11 version is a string",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: field_is("version", is_string),
+    }
+
+    Nip11IconIsUrl {
+        name: "NIP-11 icon is a URL",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: field_is("icon", is_http_url),
+    }
+
+    Nip11BannerIsUrl {
+        name: "NIP-11 banner is a URL",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: field_is("banner", is_http_url),
+    }
+
+    Nip11LimitationIsWellFormed {
+        name: "NIP-11 limitation is well formed",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: field_is("limitation", is_limitation),
+    }
+
+    Nip11RetentionIsWellFormed {
+        name: "NIP-11 retention is well formed",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: field_is("retention", is_retention),
+    }
+
+    Nip11FeesAreWellFormed {
+        name: "NIP-11 fees are well formed",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: field_is("fees", is_fees),
+    }
+
+    Nip11HasNoUnknownKeys {
+        name: "NIP-11 document has no unknown keys",
+        nips: [11],
+        required: false,
+        stage: Preauth,
+        prerequisites: [Nip11Provided],
+        run: no_unknown_keys,
+    }
+}
+
+/// The top-level keys NIP-11 defines
+const KNOWN_KEYS: &[&str] = &[
+    "name",
+    "description",
+    "banner",
+    "icon",
+    "pubkey",
+    "self",
+    "contact",
+    "supported_nips",
+    "software",
+    "version",
+    "privacy_policy",
+    "terms_of_service",
+    "limitation",
+    "retention",
+    "relay_countries",
+    "language_tags",
+    "tags",
+    "posting_policy",
+    "payments_url",
+    "fees",
+];
+
+const LIMITATION_INTEGERS: &[&str] = &[
+    "max_message_length",
+    "max_subscriptions",
+    "max_filters",
+    "max_limit",
+    "max_subid_length",
+    "max_event_tags",
+    "max_content_length",
+    "min_pow_difficulty",
+    "created_at_lower_limit",
+    "created_at_upper_limit",
+    "default_limit",
+];
+
+const LIMITATION_BOOLEANS: &[&str] = &["auth_required", "payment_required", "restricted_writes"];
+
+pub async fn content_type(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let headers = match ctx.nip11_headers {
+        Some(ref headers) => headers,
+        None => return Ok(Outcome::fail(Some("No NIP-11 response".to_owned()))),
+    };
+
+    let content_type = match headers.get("content-type").and_then(|v| v.to_str().ok()) {
+        Some(v) => v.to_ascii_lowercase(),
+        None => return Ok(Outcome::fail(Some("No Content-Type".to_owned()))),
+    };
+
+    // Ignore parameters such as charset
+    match content_type.split(';').next().map(|s| s.trim()) {
+        Some("application/nostr+json") => Ok(Outcome::pass(None)),
+        Some("application/json") => {
+            Ok(Outcome::pass(Some("Served as application/json".to_owned())))
+        }
+        _ => Ok(Outcome::fail(Some(format!(
+            "Content-Type is {}",
+            content_type
+        )))),
+    }
+}
+
+pub async fn cors_header(ctx: &mut RunContext, header: &'static str) -> Result<Outcome, Error> {
+    let headers = match ctx.nip11_headers {
+        Some(ref headers) => headers,
+        None => return Ok(Outcome::fail(Some("No NIP-11 response".to_owned()))),
+    };
+
+    match headers.get(header).and_then(|v| v.to_str().ok()) {
+        Some(v) if !v.trim().is_empty() => Ok(Outcome::pass(Some(v.to_owned()))),
+        _ => Ok(Outcome::fail(None)),
+    }
+}
+
+pub async fn field_is(
+    ctx: &mut RunContext,
+    key: &'static str,
+    check: fn(&Value) -> Result<(), String>,
+) -> Result<Outcome, Error> {
+    let document = match ctx.nip11 {
+        Some(Value::Object(ref map)) => map,
+        Some(_) => {
+            return Ok(Outcome::fail(Some(
+                "NIP-11 document is not an object".to_owned(),
+            )))
+        }
+        None => {
+            return Ok(Outcome::fail(Some(
+                "NIP-11 document was not found".to_owned(),
+            )))
+        }
+    };
+
+    match document.get(key) {
+        None => Ok(Outcome::skipped(format!("No {} given", key))),
+        Some(value) => match check(value) {
+            Ok(()) => Ok(Outcome::pass(None)),
+            Err(problem) => Ok(Outcome::fail(Some(problem))),
+        },
+    }
+}
+
+pub async fn no_unknown_keys(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let document = match ctx.nip11 {
+        Some(Value::Object(ref map)) => map,
+        _ => {
+            return Ok(Outcome::fail(Some(
+                "NIP-11 document is not an object".to_owned(),
+            )))
+        }
+    };
+
+    let unknown: Vec<String> = document
+        .keys()
+        .filter(|k| !KNOWN_KEYS.contains(&k.as_str()))
+        .map(|k| match nearest_known_key(k) {
+            Some(known) => format!("{} (did you mean {}?)", k, known),
+            None => k.to_owned(),
+        })
+        .collect();
+
+    if unknown.is_empty() {
+        Ok(Outcome::pass(None))
+    } else {
+        Ok(Outcome::fail(Some(format!(
+            "Unknown keys: {}",
+            unknown.join(", ")
+        ))))
+    }
+}
+
+fn is_string(value: &Value) -> Result<(), String> {
+    match value {
+        Value::String(_) => Ok(()),
+        other => Err(format!("Not a string: {}", other)),
+    }
+}
+
+fn is_hex_key(value: &Value) -> Result<(), String> {
+    match value.as_str() {
+        Some(s) if s.len() == 64 && s.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')) => Ok(()),
+        _ => Err(format!("Not 64 lowercase hex digits: {}", value)),
+    }
+}
+
+fn is_http_url(value: &Value) -> Result<(), String> {
+    match value.as_str() {
+        Some(s) if s.starts_with("https://") || s.starts_with("http://") => Ok(()),
+        _ => Err(format!("Not an http(s) URL: {}", value)),
+    }
+}
+
+fn is_integer_list(value: &Value) -> Result<(), String> {
+    match value.as_array() {
+        Some(array) => match array.iter().find(|v| !v.is_u64()) {
+            Some(v) => Err(format!("Not an integer: {}", v)),
+            None => Ok(()),
+        },
+        None => Err(format!("Not a list: {}", value)),
+    }
+}
+
+// Only the known keys are checked: relays may add their own
+fn is_limitation(value: &Value) -> Result<(), String> {
+    let map = as_object(value)?;
+    for (key, v) in map.iter() {
+        if LIMITATION_INTEGERS.contains(&key.as_str()) {
+            if !v.is_u64() {
+                return Err(format!("{} is not a non-negative integer: {}", key, v));
+            }
+        } else if LIMITATION_BOOLEANS.contains(&key.as_str()) && !v.is_boolean() {
+            return Err(format!("{} is not a boolean: {}", key, v));
+        }
+    }
+    Ok(())
+}
+
+// A list of {"kinds": [kind or [from, to], ...], "time": secs or null, "count": n}
+fn is_retention(value: &Value) -> Result<(), String> {
+    let rules = value
+        .as_array()
+        .ok_or_else(|| format!("Not a list: {}", value))?;
+    for rule in rules {
+        let map = as_object(rule)?;
+        if let Some(kinds) = map.get("kinds") {
+            let kinds = kinds
+                .as_array()
+                .ok_or_else(|| format!("kinds is not a list: {}", kinds))?;
+            for kind in kinds {
+                let ok = kind.is_u64()
+                    || kind
+                        .as_array()
+                        .map(|r| r.len() == 2 && r.iter().all(|k| k.is_u64()))
+                        .unwrap_or(false);
+                if !ok {
+                    return Err(format!("Not a kind or kind range: {}", kind));
+                }
+            }
+        }
+        if let Some(time) = map.get("time") {
+            if !time.is_null() && !time.is_u64() {
+                return Err(format!("time is not an integer or null: {}", time));
+            }
+        }
+        if let Some(count) = map.get("count") {
+            if !count.is_u64() {
+                return Err(format!("count is not an integer: {}", count));
+            }
+        }
+    }
+    Ok(())
+}
+
+// {"admission": [fee, ...], "subscription": [...], "publication": [...]}, each
+// fee being {"amount": n, "unit": "msats", ...}
+fn is_fees(value: &Value) -> Result<(), String> {
+    let map = as_object(value)?;
+    for (category, fees) in map.iter() {
+        let fees = fees
+            .as_array()
+            .ok_or_else(|| format!("{} is not a list: {}", category, fees))?;
+        for fee in fees {
+            let fee = as_object(fee)?;
+            match fee.get("amount") {
+                Some(amount) if amount.is_u64() => {}
+                _ => return Err(format!("{} fee has no integer amount", category)),
+            }
+            match fee.get("unit") {
+                Some(Value::String(_)) => {}
+                _ => return Err(format!("{} fee has no unit", category)),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn as_object(value: &Value) -> Result<&Map<String, Value>, String> {
+    value
+        .as_object()
+        .ok_or_else(|| format!("Not an object: {}", value))
+}
+
+// The known key a misspelt key was probably meant to be
+fn nearest_known_key(key: &str) -> Option<&'static str> {
+    KNOWN_KEYS
+        .iter()
+        .map(|known| (edit_distance(key, known), *known))
+        .filter(|(distance, _)| *distance <= 2)
+        .min()
+        .map(|(_, known)| known)
+}
+
+// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(previous + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
@@ -1,23 +1,51 @@
 use super::tags;
+use crate::context::{EventParts, RunContext, User};
 use crate::error::Error;
-use crate::globals::{EventParts, Globals, User, GLOBALS};
 use crate::outcome::Outcome;
-use crate::WAIT;
 use nostr_types::EventKind;
-use std::time::Duration;
 
-pub async fn public_can_write() -> Result<Outcome, Error> {
-    let event = Globals::make_event(
+relay_test! {
+    PublicCanWrite {
+        name: "Public can write",
+        required: false,
+        stage: Preauth,
+        run: public_can_write,
+    }
+
+    AcceptsRelayListsFromPublic {
+        name: "Accepts relay lists from the public",
+        nips: [65],
+        required: false,
+        stage: Preauth,
+        run: accepts_relay_lists_from_public,
+    }
+
+    AcceptsDmRelayListsFromPublic {
+        name: "Accepts DM relay lists from the public",
+        nips: [17],
+        required: false,
+        stage: Preauth,
+        run: accepts_dm_relay_lists_from_public,
+    }
+
+    AcceptsEphemeralEventsFromPublic {
+        name: "Accepts ephemeral events from the public",
+        nips: [1],
+        required: false,
+        stage: Preauth,
+        run: accepts_ephemeral_events_from_public,
+    }
+}
+
+pub async fn public_can_write(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let event = ctx.make_event(
         EventParts::Basic(EventKind::TextNote, tags(&[&["test"]]), "".to_string()),
         User::Stranger,
     )?;
 
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
 
     if ok {
@@ -27,18 +55,15 @@ pub async fn public_can_write() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn accepts_relay_lists_from_public() -> Result<Outcome, Error> {
-    let event = Globals::make_event(
+pub async fn accepts_relay_lists_from_public(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let event = ctx.make_event(
         EventParts::Basic(EventKind::RelayList, tags(&[&["test"]]), "".to_string()),
         User::Stranger,
     )?;
 
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
 
     if ok {
@@ -48,18 +73,15 @@ pub async fn accepts_relay_lists_from_public() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn accepts_dm_relay_lists_from_public() -> Result<Outcome, Error> {
-    let event = Globals::make_event(
+pub async fn accepts_dm_relay_lists_from_public(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let event = ctx.make_event(
         EventParts::Basic(EventKind::DmRelayList, tags(&[&["test"]]), "".to_string()),
         User::Stranger,
     )?;
 
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
 
     if ok {
@@ -69,8 +91,8 @@ pub async fn accepts_dm_relay_lists_from_public() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn accepts_ephemeral_events_from_public() -> Result<Outcome, Error> {
-    let event = Globals::make_event(
+pub async fn accepts_ephemeral_events_from_public(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let event = ctx.make_event(
         EventParts::Basic(
             EventKind::WalletResponse,
             tags(&[&["test"]]),
@@ -79,12 +101,9 @@ pub async fn accepts_ephemeral_events_from_public() -> Result<Outcome, Error> {
         User::Stranger,
     )?;
 
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
 
     if ok {
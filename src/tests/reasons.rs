@@ -0,0 +1,184 @@
+use super::nip11::claims_nip;
+use super::tags;
+use crate::connection::{Reason, ReasonPrefix};
+use crate::connections::Role;
+use crate::context::{EventParts, RunContext, User};
+use crate::error::Error;
+use crate::outcome::Outcome;
+use nostr_types::{Event, EventKind, Filter, Id, Signature, Signer};
+
+// These check the machine-readable prefixes of OK and CLOSED messages
+relay_test! {
+    BadSignatureReasonIsInvalid {
+        name: "Rejects a bad signature with invalid:",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: bad_signature_reason_is_invalid,
+    }
+
+    BadIdReasonIsInvalid {
+        name: "Rejects a bad id hash with invalid:",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: bad_id_reason_is_invalid,
+    }
+
+    DuplicateReasonIsDuplicate {
+        name: "Accepts a duplicate with duplicate:",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: duplicate_reason_is_duplicate,
+    }
+
+    GiftwrapReqClosedWithAuthRequired {
+        name: "Closes an unauthenticated giftwrap REQ with auth-required:",
+        nips: [1, 42, 59],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: giftwrap_req_closed_with_auth_required,
+    }
+}
+
+pub async fn bad_signature_reason_is_invalid(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let mut event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            tags(&[&["test"]]),
+            "This has a bad signature".to_string(),
+        ),
+        User::Registered1,
+    )?;
+
+    event.sig = Signature::zeroes();
+
+    rejected_with(ctx, event, ReasonPrefix::Invalid).await
+}
+
+pub async fn bad_id_reason_is_invalid(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let mut event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            tags(&[&["test"]]),
+            "This has a bad id hash".to_string(),
+        ),
+        User::Registered1,
+    )?;
+
+    event.id =
+        Id::try_from_hex_string("deadbeefdeadbeefdeadbeefdeadbeefdeadbeefdeadbeefdeadbeefdeadbeef")
+            .unwrap();
+    event.sig = ctx.registered1.sign_id(event.id).unwrap();
+
+    rejected_with(ctx, event, ReasonPrefix::Invalid).await
+}
+
+pub async fn duplicate_reason_is_duplicate(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let event = ctx.make_event(
+        EventParts::Basic(
+            EventKind::TextNote,
+            vec![],
+            "This will be sent twice".to_string(),
+        ),
+        User::Registered1,
+    )?;
+
+    let (ok, reason) = ctx
+        .connection
+        .post_event(event.clone(), ctx.timeouts().wait())
+        .await?;
+    if !ok {
+        return Ok(Outcome::prerequisite_failed(reason));
+    }
+
+    let (ok, reason) = ctx
+        .connection
+        .post_event(event, ctx.timeouts().wait())
+        .await?;
+    let reason = Reason::parse(&reason);
+
+    // NIP-01 has the relay say it already has the event, not refuse it
+    match (ok, reason.prefix) {
+        (true, Some(ReasonPrefix::Duplicate)) => Ok(Outcome::pass(None)),
+        (false, Some(ReasonPrefix::Duplicate)) => Ok(Outcome::fail(Some(
+            "Answered the duplicate with false rather than true".to_owned(),
+        ))),
+        _ => Ok(Outcome::fail(Some(format!(
+            "Answered the duplicate with {} \"{}\"",
+            ok, reason
+        )))),
+    }
+}
+
+pub async fn giftwrap_req_closed_with_auth_required(
+    ctx: &mut RunContext,
+) -> Result<Outcome, Error> {
+    match ctx.nip11 {
+        None => return Ok(Outcome::skipped("NIP-11 document was not found".to_owned())),
+        Some(ref nip11) if !claims_nip(nip11, 42) => {
+            return Ok(Outcome::skipped(
+                "Relay does not claim to support AUTH".to_owned(),
+            ))
+        }
+        _ => {}
+    }
+
+    let filter = {
+        let mut filter = Filter::new();
+        filter.add_event_kind(EventKind::GiftWrap);
+        filter.add_tag_value('p', ctx.registered1.public_key().as_hex_string());
+        filter
+    };
+
+    // A connection that ignores any AUTH challenge
+    let wait = ctx.timeouts().wait();
+    let anonymous = ctx.connect("anonymous", Role::Anonymous).await?;
+    let result = anonymous.fetch_events(filter, wait).await?;
+
+    match result.close_reason() {
+        Some(reason) if reason.prefix == Some(ReasonPrefix::AuthRequired) => {
+            Ok(Outcome::pass(None))
+        }
+        Some(reason) => Ok(Outcome::fail(Some(format!(
+            "Closed without auth-required: {}",
+            reason
+        )))),
+        None if result.post_eose_events.is_some() => Ok(Outcome::fail(Some(
+            "Served giftwraps without AUTH".to_owned(),
+        ))),
+        None => Ok(Outcome::fail(Some(
+            "No answer to the giftwrap REQ".to_owned(),
+        ))),
+    }
+}
+
+// Post the event, which the relay should reject with the given prefix
+async fn rejected_with(
+    ctx: &mut RunContext,
+    event: Event,
+    expected: ReasonPrefix,
+) -> Result<Outcome, Error> {
+    let (ok, reason) = ctx
+        .connection
+        .post_event(event, ctx.timeouts().wait())
+        .await?;
+    let reason = Reason::parse(&reason);
+
+    if ok {
+        Ok(Outcome::fail(Some(format!(
+            "Accepted the event: {}",
+            reason
+        ))))
+    } else if reason.prefix == Some(expected) {
+        Ok(Outcome::pass(None))
+    } else {
+        Ok(Outcome::fail(Some(format!(
+            "Expected {}: but got \"{}\"",
+            expected.as_ref(),
+            reason
+        ))))
+    }
+}
@@ -1,33 +1,55 @@
 use super::tags;
+use crate::context::{EventParts, RunContext, User};
 use crate::error::Error;
-use crate::globals::{EventParts, Globals, User, GLOBALS};
 use crate::outcome::Outcome;
-use crate::WAIT;
 use nostr_types::{EventKind, Id, Signature, Signer};
-use std::sync::atomic::Ordering;
-use std::time::Duration;
 
-pub async fn sends_ok_after_event() -> Result<Outcome, Error> {
-    Ok(match GLOBALS.saw_ok_after_event.load(Ordering::Relaxed) {
+relay_test! {
+    // This checks whether an OK was ever seen, so something must have been posted
+    SendsOkAfterEvent {
+        name: "Sends OK after EVENT",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        prerequisites: [PublicCanWrite],
+        run: sends_ok_after_event,
+    }
+
+    VerifiesSignatures {
+        name: "Verifies event signatures",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: verifies_signatures,
+    }
+
+    VerifiesIdHashes {
+        name: "Verifies event ID hashes",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: verifies_id_hashes,
+    }
+}
+
+pub async fn sends_ok_after_event(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    Ok(match ctx.connection.saw_ok_after_event {
         true => Outcome::pass(None),
         false => Outcome::fail(None),
     })
 }
 
-pub async fn verifies_signatures() -> Result<Outcome, Error> {
-    let mut event = Globals::make_event(
+pub async fn verifies_signatures(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let mut event = ctx.make_event(
         EventParts::Basic(EventKind::TextNote, tags(&[&["test"]]), "".to_string()),
         User::Registered1,
     )?;
 
     event.sig = Signature::zeroes();
 
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
 
     if ok {
@@ -39,8 +61,8 @@ pub async fn verifies_signatures() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn verifies_id_hashes() -> Result<Outcome, Error> {
-    let mut event = Globals::make_event(
+pub async fn verifies_id_hashes(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let mut event = ctx.make_event(
         EventParts::Basic(EventKind::TextNote, tags(&[&["test"]]), "".to_string()),
         User::Registered1,
     )?;
@@ -48,14 +70,11 @@ pub async fn verifies_id_hashes() -> Result<Outcome, Error> {
     event.id =
         Id::try_from_hex_string("cafebabecafebabecafebabecafebabecafebabecafebabecafebabecafebabe")
             .unwrap();
-    event.sig = GLOBALS.registered1.read().sign_id(event.id).unwrap();
+    event.sig = ctx.registered1.sign_id(event.id).unwrap();
 
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
 
     if ok {
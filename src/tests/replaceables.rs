@@ -1,27 +1,103 @@
 use super::maybe_submit_event_group_a;
+use crate::context::RunContext;
 use crate::error::Error;
-use crate::globals::GLOBALS;
 use crate::outcome::Outcome;
-use crate::WAIT;
 use nostr_types::{Event, EventKind, Filter, Id, Signer};
-use std::time::Duration;
 
-pub async fn accepts_metadata() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
-    let metadata_older_id: Id = GLOBALS
-        .event_group_a
-        .read()
-        .get("metadata_older")
-        .unwrap()
-        .0
-        .id;
-    let metadata_newer_id: Id = GLOBALS
-        .event_group_a
-        .read()
-        .get("metadata_newer")
-        .unwrap()
-        .0
-        .id;
+relay_test! {
+    AcceptsMetadata {
+        name: "Accepts metadata",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: accepts_metadata,
+    }
+
+    ReplacesMetadata {
+        name: "Replaces metadata",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: replaces_metadata,
+    }
+
+    AcceptsContactlist {
+        name: "Accepts Contactlists",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: accepts_contact_list,
+    }
+
+    ReplacesContactlist {
+        name: "Replaces Contactlists",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: replaces_contact_list,
+    }
+
+    ReplacedEventsStillAvailableById {
+        name: "Replaced events are still available by ID",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: replaced_events_still_available_by_id,
+    }
+
+    ReplaceableEventRemovesPrevious {
+        name: "Replaceable events replace older ones",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: replaceable_event_removes_previous,
+    }
+
+    ReplaceableEventRejectedIfFuture {
+        name: "Replaceable events rejected if a newer one exists",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: replaceable_event_rejected_if_future,
+    }
+
+    AddressableEventRemovesPrevious {
+        name: "Addressable events replace older ones",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: addressable_event_removes_previous,
+    }
+
+    AddressableEventRejectedIfFuture {
+        name: "Addressable events rejected if a newer one exists",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: addressable_event_rejected_if_future,
+    }
+
+    FindReplaceableEvent {
+        name: "Finds replaceable events",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: find_replaceable_event,
+    }
+
+    FindAddressableEvent {
+        name: "Finds addressable events",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: find_addressable_event,
+    }
+}
+
+pub async fn accepts_metadata(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+    let metadata_older_id: Id = ctx.event_group_a.get("metadata_older").unwrap().0.id;
+    let metadata_newer_id: Id = ctx.event_group_a.get("metadata_newer").unwrap().0.id;
 
     let filter = {
         let mut filter = Filter::new();
@@ -29,12 +105,9 @@ pub async fn accepts_metadata() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -47,24 +120,12 @@ pub async fn accepts_metadata() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn replaces_metadata() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
-    let metadata_older_id: Id = GLOBALS
-        .event_group_a
-        .read()
-        .get("metadata_older")
-        .unwrap()
-        .0
-        .id;
-    let metadata_newer_id: Id = GLOBALS
-        .event_group_a
-        .read()
-        .get("metadata_newer")
-        .unwrap()
-        .0
-        .id;
+pub async fn replaces_metadata(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+    let metadata_older_id: Id = ctx.event_group_a.get("metadata_older").unwrap().0.id;
+    let metadata_newer_id: Id = ctx.event_group_a.get("metadata_newer").unwrap().0.id;
 
-    let registered_public_key = GLOBALS.registered1.read().public_key();
+    let registered_public_key = ctx.registered1.public_key();
 
     let filter = {
         let mut filter = Filter::new();
@@ -73,12 +134,9 @@ pub async fn replaces_metadata() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -97,22 +155,10 @@ pub async fn replaces_metadata() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn accepts_contact_list() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
-    let contactlist_older_id: Id = GLOBALS
-        .event_group_a
-        .read()
-        .get("contactlist_older")
-        .unwrap()
-        .0
-        .id;
-    let contactlist_newer_id: Id = GLOBALS
-        .event_group_a
-        .read()
-        .get("contactlist_newer")
-        .unwrap()
-        .0
-        .id;
+pub async fn accepts_contact_list(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+    let contactlist_older_id: Id = ctx.event_group_a.get("contactlist_older").unwrap().0.id;
+    let contactlist_newer_id: Id = ctx.event_group_a.get("contactlist_newer").unwrap().0.id;
 
     let filter = {
         let mut filter = Filter::new();
@@ -120,12 +166,9 @@ pub async fn accepts_contact_list() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -138,24 +181,12 @@ pub async fn accepts_contact_list() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn replaces_contact_list() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
-    let contactlist_older_id: Id = GLOBALS
-        .event_group_a
-        .read()
-        .get("contactlist_older")
-        .unwrap()
-        .0
-        .id;
-    let contactlist_newer_id: Id = GLOBALS
-        .event_group_a
-        .read()
-        .get("contactlist_newer")
-        .unwrap()
-        .0
-        .id;
+pub async fn replaces_contact_list(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+    let contactlist_older_id: Id = ctx.event_group_a.get("contactlist_older").unwrap().0.id;
+    let contactlist_newer_id: Id = ctx.event_group_a.get("contactlist_newer").unwrap().0.id;
 
-    let registered_public_key = GLOBALS.registered1.read().public_key();
+    let registered_public_key = ctx.registered1.public_key();
 
     let filter = {
         let mut filter = Filter::new();
@@ -164,12 +195,9 @@ pub async fn replaces_contact_list() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -190,15 +218,9 @@ pub async fn replaces_contact_list() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn replaced_events_still_available_by_id() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
-    let contactlist_older_id: Id = GLOBALS
-        .event_group_a
-        .read()
-        .get("contactlist_older")
-        .unwrap()
-        .0
-        .id;
+pub async fn replaced_events_still_available_by_id(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+    let contactlist_older_id: Id = ctx.event_group_a.get("contactlist_older").unwrap().0.id;
 
     let filter = {
         let mut filter = Filter::new();
@@ -206,12 +228,9 @@ pub async fn replaced_events_still_available_by_id() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -224,16 +243,10 @@ pub async fn replaced_events_still_available_by_id() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn replaceable_event_removes_previous() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn replaceable_event_removes_previous(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
-    let older_replaceable_id: Id = GLOBALS
-        .event_group_a
-        .read()
-        .get("older_replaceable")
-        .unwrap()
-        .0
-        .id;
+    let older_replaceable_id: Id = ctx.event_group_a.get("older_replaceable").unwrap().0.id;
 
     let filter = {
         let mut filter = Filter::new();
@@ -241,12 +254,9 @@ pub async fn replaceable_event_removes_previous() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -260,23 +270,19 @@ pub async fn replaceable_event_removes_previous() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn replaceable_event_rejected_if_future() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn replaceable_event_rejected_if_future(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
-    let event: Event = GLOBALS
+    let event: Event = ctx
         .event_group_a
-        .read()
         .get("older_replaceable")
         .unwrap()
         .0
         .clone();
 
-    let (ok, _reason) = GLOBALS
+    let (ok, _reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
 
     if ok {
@@ -290,12 +296,11 @@ pub async fn replaceable_event_rejected_if_future() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn addressable_event_removes_previous() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn addressable_event_removes_previous(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
-    let older_param_replaceable_id: Id = GLOBALS
+    let older_param_replaceable_id: Id = ctx
         .event_group_a
-        .read()
         .get("older_param_replaceable")
         .unwrap()
         .0
@@ -307,12 +312,9 @@ pub async fn addressable_event_removes_previous() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -326,23 +328,19 @@ pub async fn addressable_event_removes_previous() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn addressable_event_rejected_if_future() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
+pub async fn addressable_event_rejected_if_future(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
 
-    let event: Event = GLOBALS
+    let event: Event = ctx
         .event_group_a
-        .read()
         .get("older_param_replaceable")
         .unwrap()
         .0
         .clone();
 
-    let (ok, _reason) = GLOBALS
+    let (ok, _reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
 
     if ok {
@@ -356,11 +354,10 @@ pub async fn addressable_event_rejected_if_future() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn find_replaceable_event() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
-    let newer_replaceable = GLOBALS
+pub async fn find_replaceable_event(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+    let newer_replaceable = ctx
         .event_group_a
-        .read()
         .get("newer_replaceable")
         .unwrap()
         .0
@@ -373,12 +370,9 @@ pub async fn find_replaceable_event() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
@@ -391,11 +385,10 @@ pub async fn find_replaceable_event() -> Result<Outcome, Error> {
     }
 }
 
-pub async fn find_addressable_event() -> Result<Outcome, Error> {
-    maybe_submit_event_group_a().await?;
-    let newer_addressable = GLOBALS
+pub async fn find_addressable_event(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_event_group_a(ctx).await?;
+    let newer_addressable = ctx
         .event_group_a
-        .read()
         .get("newer_param_replaceable")
         .unwrap()
         .0
@@ -408,12 +401,9 @@ pub async fn find_addressable_event() -> Result<Outcome, Error> {
         filter
     };
 
-    let events = GLOBALS
+    let events = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .fetch_events(filter, Duration::from_secs(WAIT))
+        .fetch_events(filter, ctx.timeouts().wait())
         .await?
         .into_events();
 
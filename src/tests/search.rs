@@ -0,0 +1,326 @@
+use super::nip11::claims_nip;
+use crate::connection::FetchResult;
+use crate::context::{EventParts, RunContext};
+use crate::error::Error;
+use crate::outcome::Outcome;
+use nostr_types::{Event, EventKind, Filter, Id, KeySigner, Signer};
+use serde_json::Value;
+
+relay_test! {
+    SearchFindsTerm {
+        name: "Search finds events containing the term",
+        nips: [50],
+        required: false,
+        stage: Registered,
+        run: search_finds_term,
+    }
+
+    SearchIsCaseInsensitive {
+        name: "Search is case insensitive",
+        nips: [50],
+        required: false,
+        stage: Registered,
+        run: search_is_case_insensitive,
+    }
+
+    SearchCombinesWithKindsAndAuthors {
+        name: "Search combines with kinds and authors",
+        nips: [50],
+        required: false,
+        stage: Registered,
+        run: search_combines_with_kinds_and_authors,
+    }
+
+    SearchHonoursLimit {
+        name: "Search honours limit",
+        nips: [50],
+        required: false,
+        stage: Registered,
+        run: search_honours_limit,
+    }
+
+    SearchToleratesExtensions {
+        name: "Search tolerates unsupported extensions",
+        nips: [50],
+        required: false,
+        stage: Registered,
+        run: search_tolerates_extensions,
+    }
+
+    ClosesUnsupportedSearch {
+        name: "Closes search if unsupported",
+        nips: [50],
+        required: false,
+        stage: Registered,
+        prerequisites: [Nip11Provided],
+        run: closes_unsupported_search,
+    }
+}
+
+pub async fn search_finds_term(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_search_group(ctx).await?;
+
+    let filter = our_authors(ctx);
+    let word = ctx.search_word.clone();
+    let result = search(ctx, filter, &word).await?;
+    let expected = ids_containing(ctx, |_| true);
+
+    judge(result, expected, false)
+}
+
+pub async fn search_is_case_insensitive(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_search_group(ctx).await?;
+
+    // The word was written in lower case in some events, upper case in others
+    let filter = our_authors(ctx);
+    let word = ctx.search_word.to_uppercase();
+    let result = search(ctx, filter, &word).await?;
+    let expected = ids_containing(ctx, |_| true);
+
+    judge(result, expected, false)
+}
+
+pub async fn search_combines_with_kinds_and_authors(
+    ctx: &mut RunContext,
+) -> Result<Outcome, Error> {
+    maybe_submit_search_group(ctx).await?;
+
+    let filter = {
+        let mut filter = Filter::new();
+        filter.add_author(ctx.registered1.public_key());
+        filter.add_event_kind(EventKind::TextNote);
+        filter
+    };
+    let word = ctx.search_word.clone();
+    let result = search(ctx, filter, &word).await?;
+
+    let pubkey = ctx.registered1.public_key();
+    let expected = ids_containing(ctx, |e| e.pubkey == pubkey && e.kind == EventKind::TextNote);
+
+    judge(result, expected, false)
+}
+
+pub async fn search_honours_limit(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_search_group(ctx).await?;
+
+    let mut filter = our_authors(ctx);
+    filter.limit = Some(2);
+    let word = ctx.search_word.clone();
+    let result = search(ctx, filter, &word).await?;
+
+    if let Some(ref msg) = result.close_msg {
+        return Ok(Outcome::fail(Some(format!("Search was closed: {}", msg))));
+    }
+
+    let found = result.into_events().len();
+    if found == 2 {
+        Ok(Outcome::pass(None))
+    } else {
+        Ok(Outcome::fail(Some(format!(
+            "Asked for 2 of {} matches, got {}",
+            ids_containing(ctx, |_| true).len(),
+            found
+        ))))
+    }
+}
+
+pub async fn search_tolerates_extensions(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    maybe_submit_search_group(ctx).await?;
+
+    // A relay may honour these (and so find fewer events), but must not choke on them
+    let terms = format!(
+        "{} domain:relay-tester.invalid language:zz",
+        ctx.search_word
+    );
+    let filter = our_authors(ctx);
+    let result = search(ctx, filter, &terms).await?;
+    let expected = ids_containing(ctx, |_| true);
+
+    judge(result, expected, true)
+}
+
+pub async fn closes_unsupported_search(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    match ctx.nip11 {
+        None => return Ok(Outcome::skipped("NIP-11 document was not found".to_owned())),
+        Some(ref nip11) if claims_nip(nip11, 50) => {
+            return Ok(Outcome::skipped(
+                "Relay claims to support search".to_owned(),
+            ))
+        }
+        _ => {}
+    }
+
+    maybe_submit_search_group(ctx).await?;
+
+    // Nothing matches this, so any event means the search was ignored
+    let terms = format!("{}nomatch", ctx.search_word);
+    let filter = our_authors(ctx);
+    let result = search(ctx, filter, &terms).await?;
+
+    if let Some(ref msg) = result.close_msg {
+        return Ok(Outcome::pass(Some(msg.to_owned())));
+    }
+
+    let found = result.into_events().len();
+    if found == 0 {
+        Ok(Outcome::pass(Some(
+            "Found nothing rather than closing".to_owned(),
+        )))
+    } else {
+        Ok(Outcome::fail(Some(format!(
+            "Ignored the search and returned {} events",
+            found
+        ))))
+    }
+}
+
+// Run the filter with a search term added
+async fn search(ctx: &mut RunContext, filter: Filter, terms: &str) -> Result<FetchResult, Error> {
+    let mut filter = serde_json::to_value(&filter)?;
+    if let Value::Object(ref mut map) = filter {
+        map.insert("search".to_owned(), terms.into());
+    }
+    ctx.connection
+        .fetch_raw_events(filter, ctx.timeouts().wait())
+        .await
+}
+
+// A filter for the authors of the search group
+fn our_authors(ctx: &RunContext) -> Filter {
+    let mut filter = Filter::new();
+    filter.add_author(ctx.registered1.public_key());
+    filter.add_author(ctx.registered2.public_key());
+    filter
+}
+
+// The search group events that contain the search word and pass the test
+fn ids_containing(ctx: &RunContext, test: impl Fn(&Event) -> bool) -> Vec<Id> {
+    let word = ctx.search_word.to_lowercase();
+    ctx.search_group
+        .iter()
+        .filter(|(e, _)| e.content.to_lowercase().contains(&word) && test(e))
+        .map(|(e, _)| e.id)
+        .collect()
+}
+
+// Check the search found exactly the expected events. If `subset_ok`,
+// finding only some of them (but nothing else) is also fine.
+fn judge(result: FetchResult, expected: Vec<Id>, subset_ok: bool) -> Result<Outcome, Error> {
+    if let Some(ref msg) = result.close_msg {
+        return Ok(Outcome::fail(Some(format!("Search was closed: {}", msg))));
+    }
+    if result.post_eose_events.is_none() {
+        return Ok(Outcome::fail(Some("No EOSE for the search".to_owned())));
+    }
+
+    let found: Vec<Id> = result.into_events().iter().map(|e| e.id).collect();
+
+    let unexpected = found.iter().filter(|id| !expected.contains(id)).count();
+    if unexpected > 0 {
+        return Ok(Outcome::fail(Some(format!(
+            "Found {} events that should not match",
+            unexpected
+        ))));
+    }
+
+    let missing = expected.iter().filter(|id| !found.contains(id)).count();
+    if missing == 0 {
+        Ok(Outcome::pass(None))
+    } else if subset_ok {
+        Ok(Outcome::pass(Some(format!(
+            "Found {} of {} events",
+            found.len(),
+            expected.len()
+        ))))
+    } else {
+        Ok(Outcome::fail(Some(format!(
+            "Missed {} of {} events",
+            missing,
+            expected.len()
+        ))))
+    }
+}
+
+async fn maybe_submit_search_group(ctx: &mut RunContext) -> Result<(), Error> {
+    if ctx.search_group_submitted {
+        // Already submitted
+        return Ok(());
+    }
+
+    if ctx.search_group_failed {
+        // Already tried and it failed
+        return Err(Error::PrerequisiteEventSubmissionFailed);
+    }
+
+    match maybe_submit_search_group_inner(ctx).await {
+        Ok(()) => {
+            ctx.search_group_submitted = true;
+            Ok(())
+        }
+        Err(e) => {
+            ctx.search_group_failed = true;
+            Err(e)
+        }
+    }
+}
+
+async fn maybe_submit_search_group_inner(ctx: &mut RunContext) -> Result<(), Error> {
+    let RunContext {
+        ref mut connection,
+        ref registered1,
+        ref registered2,
+        ref mut search_group,
+        ref search_word,
+        ..
+    } = *ctx;
+
+    let lower = search_word.to_lowercase();
+    let upper = search_word.to_uppercase();
+
+    let events: [(&'static str, &KeySigner, EventKind, String); 5] = [
+        (
+            "lower_case_note",
+            registered1,
+            EventKind::TextNote,
+            format!("A note about {} in lower case", lower),
+        ),
+        (
+            "upper_case_note",
+            registered1,
+            EventKind::TextNote,
+            format!("A NOTE ABOUT {} IN UPPER CASE", upper),
+        ),
+        (
+            "other_kind",
+            registered1,
+            EventKind::Other(9998),
+            format!("Something else about {}", lower),
+        ),
+        (
+            "other_author",
+            registered2,
+            EventKind::TextNote,
+            format!("Another note about {}", lower),
+        ),
+        (
+            "no_match",
+            registered1,
+            EventKind::TextNote,
+            "A note about nothing in particular".to_owned(),
+        ),
+    ];
+
+    for (key, signer, kind, content) in events {
+        search_group
+            .insert(
+                connection,
+                signer,
+                key,
+                EventParts::Basic(kind, vec![], content),
+                true,
+            )
+            .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,313 @@
+use super::tags;
+use crate::connections::Role;
+use crate::context::{EventParts, RunContext, User};
+use crate::error::Error;
+use crate::outcome::Outcome;
+use nostr_types::{ClientMessage, Event, EventKind, Filter, RelayMessage, Signer, SubscriptionId};
+
+// These hold a subscription open on the main connection, and publish the
+// events it should (or should not) receive from a second one
+relay_test! {
+    ServesPostEoseEvents {
+        name: "Serves post-EOSE events",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: serves_post_eose_events,
+    }
+
+    NoTimeoutWhileSubscribed {
+        name: "No timeout while subscribed",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: no_timeout_while_subscribed,
+    }
+
+    ReqReplacesSubscription {
+        name: "REQ with an existing subscription id replaces it",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: req_replaces_subscription,
+    }
+
+    CloseStopsEvents {
+        name: "No events arrive after CLOSE",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: close_stops_events,
+    }
+
+    CloseUnknownSubscription {
+        name: "CLOSE of an unknown subscription is harmless",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: close_unknown_subscription,
+    }
+}
+
+pub async fn serves_post_eose_events(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let marker = marker();
+
+    let filter = {
+        let mut filter = Filter::new();
+        filter.add_author(ctx.registered2.public_key());
+        filter.add_event_kind(EventKind::TextNote);
+        filter.add_tag_value('t', marker.clone());
+        filter
+    };
+    let fresult = ctx
+        .connection
+        .fetch_events_keep_open(filter, ctx.timeouts().wait())
+        .await?;
+    let sub_id = match open_sub_id(&fresult.sub_id, &fresult.close_msg) {
+        Ok(sub_id) => sub_id,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    // Published by someone else, from elsewhere
+    let published = match publish(ctx, User::Registered2, &[&marker]).await? {
+        Ok(published) => published,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    let received = receive(ctx, sub_id).await?;
+    if received.iter().any(|e| e.id == published[0].id) {
+        Ok(Outcome::pass(None))
+    } else {
+        Ok(Outcome::fail(Some(
+            "The event was not delivered to the open subscription".to_owned(),
+        )))
+    }
+}
+
+pub async fn no_timeout_while_subscribed(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let fresult = ctx
+        .connection
+        .fetch_events_keep_open(marked(ctx, &marker()), ctx.timeouts().wait())
+        .await?;
+    let sub_id = match open_sub_id(&fresult.sub_id, &fresult.close_msg) {
+        Ok(sub_id) => sub_id,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    // Say nothing, and see whether the relay gives up on us
+    let idle = ctx.timeouts().idle();
+    let deadline = tokio::time::Instant::now() + idle;
+    loop {
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        match ctx.connection.wait_for_message(remaining).await {
+            Ok(None) => break,
+            Ok(Some(RelayMessage::Closed(sub, msg))) if sub == sub_id => {
+                return Ok(Outcome::fail(Some(format!(
+                    "Closed the subscription after {}ms idle: {}",
+                    idle.as_millis() - remaining.as_millis(),
+                    msg
+                ))))
+            }
+            Ok(Some(_)) => continue,
+            Err(Error::Disconnected) => {
+                return Ok(Outcome::fail(Some(format!(
+                    "Disconnected after {}ms idle",
+                    idle.as_millis() - remaining.as_millis()
+                ))))
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    ctx.connection.close_subscription(sub_id).await?;
+    Ok(Outcome::pass(Some(format!(
+        "Kept the subscription through {}ms idle",
+        idle.as_millis()
+    ))))
+}
+
+pub async fn req_replaces_subscription(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let old_marker = marker();
+    let new_marker = marker();
+
+    let fresult = ctx
+        .connection
+        .fetch_events_keep_open(marked(ctx, &old_marker), ctx.timeouts().wait())
+        .await?;
+    let sub_id = match open_sub_id(&fresult.sub_id, &fresult.close_msg) {
+        Ok(sub_id) => sub_id,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    // Same id, different filter
+    ctx.connection
+        .send_message(ClientMessage::Req(sub_id.clone(), marked(ctx, &new_marker)))
+        .await?;
+    let fresult = ctx
+        .connection
+        .receive_events(sub_id.clone(), ctx.timeouts().wait(), false)
+        .await?;
+    if let Some(msg) = fresult.close_msg {
+        return Ok(Outcome::fail(Some(format!(
+            "Closed the replacement subscription: {}",
+            msg
+        ))));
+    }
+
+    let published = match publish(ctx, User::Registered1, &[&old_marker, &new_marker]).await? {
+        Ok(published) => published,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    let received = receive(ctx, sub_id).await?;
+    let got_old = received.iter().any(|e| e.id == published[0].id);
+    let got_new = received.iter().any(|e| e.id == published[1].id);
+
+    match (got_old, got_new) {
+        (false, true) => Ok(Outcome::pass(None)),
+        (true, true) => Ok(Outcome::fail(Some(
+            "The old filter still matched alongside the new one".to_owned(),
+        ))),
+        (true, false) => Ok(Outcome::fail(Some(
+            "The old filter was kept and the new one ignored".to_owned(),
+        ))),
+        (false, false) => Ok(Outcome::fail(Some(
+            "Neither filter matched after the replacement".to_owned(),
+        ))),
+    }
+}
+
+pub async fn close_stops_events(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let marker = marker();
+
+    let fresult = ctx
+        .connection
+        .fetch_events_keep_open(marked(ctx, &marker), ctx.timeouts().wait())
+        .await?;
+    let sub_id = match open_sub_id(&fresult.sub_id, &fresult.close_msg) {
+        Ok(sub_id) => sub_id,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    ctx.connection.close_subscription(sub_id.clone()).await?;
+
+    if let Err(outcome) = publish(ctx, User::Registered1, &[&marker]).await? {
+        return Ok(outcome);
+    }
+
+    // Not receive(), which would CLOSE it again (CloseUnknownSubscription
+    // tests how that is answered)
+    let received = ctx
+        .connection
+        .collect_events(sub_id, ctx.timeouts().wait())
+        .await?;
+    if received.is_empty() {
+        Ok(Outcome::pass(None))
+    } else {
+        Ok(Outcome::fail(Some(format!(
+            "Received {} events after CLOSE",
+            received.len()
+        ))))
+    }
+}
+
+pub async fn close_unknown_subscription(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    let sub_id = SubscriptionId(format!("unknown{}", marker()));
+    ctx.connection.close_subscription(sub_id).await?;
+
+    // The connection should still serve us
+    let filter = {
+        let mut filter = Filter::new();
+        filter.add_author(ctx.registered1.public_key());
+        filter.add_event_kind(EventKind::TextNote);
+        filter.limit = Some(1);
+        filter
+    };
+    match ctx
+        .connection
+        .fetch_events(filter, ctx.timeouts().wait())
+        .await
+    {
+        Ok(fresult) if fresult.post_eose_events.is_some() => Ok(Outcome::pass(None)),
+        Ok(_) => Ok(Outcome::fail(Some(
+            "No EOSE for a REQ after the CLOSE".to_owned(),
+        ))),
+        Err(Error::Disconnected) => Ok(Outcome::fail(Some(
+            "Disconnected after the CLOSE".to_owned(),
+        ))),
+        Err(e) => Err(e),
+    }
+}
+
+// A tag value of our own, so nothing else matches
+fn marker() -> String {
+    format!("sub{:08x}", rand::random::<u32>())
+}
+
+// A filter for our text notes with the marker
+fn marked(ctx: &RunContext, marker: &str) -> Filter {
+    let mut filter = Filter::new();
+    filter.add_author(ctx.registered1.public_key());
+    filter.add_event_kind(EventKind::TextNote);
+    filter.add_tag_value('t', marker.to_owned());
+    filter
+}
+
+// The id of a subscription left open, or the outcome if it was not
+fn open_sub_id(
+    sub_id: &Option<SubscriptionId>,
+    close_msg: &Option<String>,
+) -> Result<SubscriptionId, Outcome> {
+    match (sub_id, close_msg) {
+        (_, Some(msg)) => Err(Outcome::prerequisite_failed(format!(
+            "The subscription was closed: {}",
+            msg
+        ))),
+        (Some(sub_id), None) => Ok(sub_id.clone()),
+        (None, None) => Err(Outcome::err("No subscription id was kept".to_owned())),
+    }
+}
+
+// Publish a text note by the user with each marker, from a connection of
+// their own. If the relay rejects one, the outcome to report.
+async fn publish(
+    ctx: &mut RunContext,
+    user: User,
+    markers: &[&str],
+) -> Result<Result<Vec<Event>, Outcome>, Error> {
+    let mut published: Vec<Event> = Vec::new();
+    for marker in markers {
+        published.push(ctx.make_event(
+            EventParts::Basic(
+                EventKind::TextNote,
+                tags(&[&["t", marker]]),
+                format!("Published for {}", marker),
+            ),
+            user,
+        )?);
+    }
+
+    let wait = ctx.timeouts().wait();
+    let publisher = ctx.connect("publisher", Role::As(user)).await?;
+    for event in published.iter() {
+        let (ok, reason) = publisher.post_event(event.clone(), wait).await?;
+        if !ok {
+            return Ok(Err(Outcome::prerequisite_failed(reason)));
+        }
+    }
+    Ok(Ok(published))
+}
+
+// The events that arrive for the subscription until things go quiet. The
+// subscription is closed afterwards.
+async fn receive(ctx: &mut RunContext, sub_id: SubscriptionId) -> Result<Vec<Event>, Error> {
+    let events = ctx
+        .connection
+        .collect_events(sub_id.clone(), ctx.timeouts().wait())
+        .await?;
+    ctx.connection.close_subscription(sub_id).await?;
+    Ok(events)
+}
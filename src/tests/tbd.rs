@@ -0,0 +1,144 @@
+use super::tbd;
+
+relay_test! {
+    UnknownCanWriteOwn {
+        name: "Unknown can write own",
+        required: true,
+        stage: Stranger,
+        run: tbd,
+    }
+
+    UnknownCanReadbackOwn {
+        name: "Unknown can read back own",
+        required: true,
+        stage: Stranger,
+        run: tbd,
+    }
+
+    UnknownCanWriteOther {
+        name: "Unknown can write other",
+        required: true,
+        stage: Stranger,
+        run: tbd,
+    }
+
+    UnknownCanReadbackOther {
+        name: "Unknown can read back other",
+        required: true,
+        stage: Stranger,
+        run: tbd,
+    }
+
+    CanAuthAsKnown {
+        name: "Can AUTH as known",
+        nips: [42],
+        required: true,
+        stage: Registered,
+        run: tbd,
+    }
+
+    KnownCanWriteOwn {
+        name: "Known can write own",
+        required: true,
+        stage: Registered,
+        run: tbd,
+    }
+
+    KnownCanReadbackOwn {
+        name: "Known can read back own",
+        required: true,
+        stage: Registered,
+        run: tbd,
+    }
+
+    KnownCanWriteOther {
+        name: "Known can write other",
+        required: true,
+        stage: Registered,
+        run: tbd,
+    }
+
+    KnownCanReadbackOther {
+        name: "Known can readback other",
+        required: true,
+        stage: Registered,
+        run: tbd,
+    }
+
+    LargeContactLists {
+        name: "Supports large contact lists",
+        nips: [2],
+        required: true,
+        stage: Registered,
+        run: tbd,
+    }
+
+    PreservesJsonFieldOrder {
+        name: "Preserves JSON field order",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: tbd,
+    }
+
+    PreservesNonstandardJsonFields {
+        name: "Preserves Non-standard JSON fields",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: tbd,
+    }
+
+    HandlesEventKindLargerThan16bit {
+        name: "Handles event.kind > 16 bit",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: tbd,
+    }
+
+    HandlesFilterKindLargerThan16bit {
+        name: "Handles filter.kinds > 16 bit",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: tbd,
+    }
+
+    AcceptsNegativeFilterCreatedAt {
+        name: "Accepts negative filter.since/until",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: tbd,
+    }
+
+    AcceptsNullCharacters {
+        name: "Accepts null character",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: tbd,
+    }
+
+    HandlesFilterPrefixes {
+        name: "Handles filter prefixes",
+        required: false,
+        stage: Registered,
+        run: tbd,
+    }
+
+    AllowsImmediateReconnect {
+        name: "Allows immediate reconnect",
+        required: false,
+        stage: Registered,
+        run: tbd,
+    }
+
+    IdleTimeoutIfUnsubscribed {
+        name: "Idle timeout if unsubscribed",
+        required: false,
+        stage: Registered,
+        run: tbd,
+    }
+}
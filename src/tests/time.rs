@@ -1,82 +1,176 @@
 use super::tags;
+use crate::context::{EventParts, RunContext, User};
 use crate::error::Error;
-use crate::globals::{EventParts, Globals, User, GLOBALS};
 use crate::outcome::Outcome;
-use crate::WAIT;
 use nostr_types::{EventKind, Unixtime};
 use std::ops::{Add, Sub};
 use std::time::Duration;
 
-pub async fn one_week_ago() -> Result<Outcome, Error> {
-    doit(minutes_ago(60 * 24 * 7)).await
+relay_test! {
+    AcceptsEventsOneWeekOld {
+        name: "Accepts event.created_at one week old",
+        nips: [1],
+        required: true,
+        stage: Registered,
+        run: one_week_ago,
+    }
+
+    AcceptsEventsOneMonthOld {
+        name: "Accepts event.created_at one month old",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: one_month_ago,
+    }
+
+    AcceptsEventsOneYearOld {
+        name: "Accepts event.created_at one year old",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: one_year_ago,
+    }
+
+    AcceptsEventsFromBeforeNostr {
+        name: "Accepts event.created_at from before nostr",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: before_nostr,
+    }
+
+    AcceptsEventsFromBefore2000 {
+        name: "Accepts event.created_at from before 2000",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: before_2000,
+    }
+
+    AcceptsEventsFrom1970 {
+        name: "Accepts event.created_at from 1970",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: from_1970,
+    }
+
+    AcceptsEventsFromBefore1970 {
+        name: "Accepts event.created_at from before 1970",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: before_1970,
+    }
+
+    AcceptsEventsOneYearIntoTheFuture {
+        name: "Accepts event.created_at one year into the future",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: one_year_hence,
+    }
+
+    AcceptsEventsInTheDistantFuture {
+        name: "Accepts event.created_at in the distant future",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: distant_future,
+    }
+
+    AcceptsEventsWithCreatedAtGreaterThanSigned32Bit {
+        name: "Accepts event.created_at greater than signed 32-bit",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: greater_than_signed_32bit,
+    }
+
+    AcceptsEventsWithCreatedAtGreaterThanUnsigned32Bit {
+        name: "Accepts event.created_at greater than unsigned 32-bit",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: greater_than_unsigned_32bit,
+    }
+
+    AcceptsEventsWithCreatedAtInScientificNotation {
+        name: "Accepts event.created_at in scientific notation",
+        nips: [1],
+        required: false,
+        stage: Registered,
+        run: scientific_notation,
+    }
+}
+
+pub async fn one_week_ago(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    doit(ctx, minutes_ago(60 * 24 * 7)).await
 }
 
-pub async fn one_month_ago() -> Result<Outcome, Error> {
-    doit(minutes_ago(60 * 24 * 7 * 4)).await
+pub async fn one_month_ago(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    doit(ctx, minutes_ago(60 * 24 * 7 * 4)).await
 }
 
-pub async fn one_year_ago() -> Result<Outcome, Error> {
-    doit(minutes_ago(60 * 24 * 365)).await
+pub async fn one_year_ago(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    doit(ctx, minutes_ago(60 * 24 * 365)).await
 }
 
-pub async fn before_nostr() -> Result<Outcome, Error> {
+pub async fn before_nostr(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // 2015, Thursday, January 1, 2015 12:01:01 AM GMT
-    doit(Unixtime(1420070461)).await
+    doit(ctx, Unixtime(1420070461)).await
 }
 
-pub async fn before_2000() -> Result<Outcome, Error> {
+pub async fn before_2000(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // 1999, Friday, January 1, 1999 12:01:01 AM
-    doit(Unixtime(915148861)).await
+    doit(ctx, Unixtime(915148861)).await
 }
 
-pub async fn from_1970() -> Result<Outcome, Error> {
+pub async fn from_1970(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // 1970, Thursday, January 1, 1970 12:00:00 AM
-    doit(Unixtime(0)).await
+    doit(ctx, Unixtime(0)).await
 }
 
-pub async fn before_1970() -> Result<Outcome, Error> {
+pub async fn before_1970(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // sometime in 1969, negative date
-    doit_raw("-200").await
+    doit_raw(ctx, "-200").await
 }
 
-pub async fn one_year_hence() -> Result<Outcome, Error> {
-    doit(Unixtime::now().add(Duration::new(86400 * 365, 0))).await
+pub async fn one_year_hence(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    doit(ctx, Unixtime::now().add(Duration::new(86400 * 365, 0))).await
 }
 
-pub async fn distant_future() -> Result<Outcome, Error> {
-    doit(Unixtime(i64::MAX)).await
+pub async fn distant_future(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    doit(ctx, Unixtime(i64::MAX)).await
 }
 
-pub async fn greater_than_signed_32bit() -> Result<Outcome, Error> {
+pub async fn greater_than_signed_32bit(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // 2^31 + 1
-    doit_raw("2147483649").await
+    doit_raw(ctx, "2147483649").await
 }
 
-pub async fn greater_than_unsigned_32bit() -> Result<Outcome, Error> {
+pub async fn greater_than_unsigned_32bit(ctx: &mut RunContext) -> Result<Outcome, Error> {
     // 2^32 + 1
-    doit_raw("4294967297").await
+    doit_raw(ctx, "4294967297").await
 }
 
-pub async fn scientific_notation() -> Result<Outcome, Error> {
-    doit_raw("1e+10").await
+pub async fn scientific_notation(ctx: &mut RunContext) -> Result<Outcome, Error> {
+    doit_raw(ctx, "1e+10").await
 }
 
 fn minutes_ago(m: u64) -> Unixtime {
     Unixtime::now().sub(Duration::new(m * 60, 0))
 }
 
-async fn doit(u: Unixtime) -> Result<Outcome, Error> {
-    let event = Globals::make_event(
+async fn doit(ctx: &mut RunContext, u: Unixtime) -> Result<Outcome, Error> {
+    let event = ctx.make_event(
         EventParts::Dated(EventKind::TextNote, tags(&[]), "".to_owned(), u),
         User::Registered1,
     )?;
 
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_event(event, Duration::from_secs(WAIT))
+        .post_event(event, ctx.timeouts().wait())
         .await?;
 
     if ok {
@@ -86,15 +180,12 @@ async fn doit(u: Unixtime) -> Result<Outcome, Error> {
     }
 }
 
-async fn doit_raw(date: &str) -> Result<Outcome, Error> {
-    let (id, raw_event) = Globals::make_raw_event(date, "1", "[]", "", User::Registered1);
+async fn doit_raw(ctx: &mut RunContext, date: &str) -> Result<Outcome, Error> {
+    let (id, raw_event) = ctx.make_raw_event(date, "1", "[]", "", User::Registered1);
 
-    let (ok, reason) = GLOBALS
+    let (ok, reason) = ctx
         .connection
-        .write()
-        .as_mut()
-        .unwrap()
-        .post_raw_event(id, raw_event, Duration::from_secs(WAIT))
+        .post_raw_event(id, raw_event, ctx.timeouts().wait())
         .await?;
 
     if ok {
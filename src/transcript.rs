@@ -0,0 +1,100 @@
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+impl Direction {
+    pub fn arrow(&self) -> &'static str {
+        match *self {
+            Direction::Sent => "-->",
+            Direction::Received => "<--",
+        }
+    }
+}
+
+/// A websocket frame as it went over the wire
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub at: SystemTime,
+    pub direction: Direction,
+
+    /// The name of the side connection it went over (see
+    /// [`Connections`](crate::connections::Connections)), or `None` for the
+    /// main connection
+    pub connection: Option<String>,
+
+    /// The text of the frame. Non-text frames are described, e.g. "CLOSE"
+    pub text: String,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since_epoch = self.at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        write!(
+            f,
+            "{}.{:03} {} ",
+            since_epoch.as_secs(),
+            since_epoch.subsec_millis(),
+            self.direction.arrow(),
+        )?;
+        if let Some(ref connection) = self.connection {
+            write!(f, "[{}] ", connection)?;
+        }
+        write!(f, "{}", self.text)
+    }
+}
+
+/// Every frame sent and received on a connection, in order
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    pub frames: Vec<Frame>,
+}
+
+impl Transcript {
+    pub fn record(&mut self, direction: Direction, text: String) {
+        self.frames.push(Frame {
+            at: SystemTime::now(),
+            direction,
+            connection: None,
+            text,
+        });
+    }
+
+    /// Mark every frame as having gone over the named side connection
+    pub fn label(&mut self, connection: &str) {
+        for frame in self.frames.iter_mut() {
+            frame.connection = Some(connection.to_owned());
+        }
+    }
+
+    /// Add frames recorded on another connection, keeping time order
+    pub fn merge(&mut self, other: Transcript) {
+        self.frames.extend(other.frames);
+        self.frames.sort_by_key(|frame| frame.at);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// One frame per line, each prefixed with the unix time (to the
+    /// millisecond) it was sent or received, and the side connection it went
+    /// over (if not the main one)
+    pub fn write(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        for frame in &self.frames {
+            writeln!(out, "{}", frame)?;
+        }
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        self.write(&mut file)
+    }
+}
@@ -0,0 +1,573 @@
+//! Runs the tests against the bundled mock relay, to catch regressions in
+//! the tester itself without a real relay.
+
+use nostr_types::PrivateKey;
+use relay_tester::config::{Config, Timeouts};
+use relay_tester::mock::{MockConfig, MockLimits, MockRelay};
+use relay_tester::selection::Selection;
+use relay_tester::{Registry, Runner};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Tests the well-behaved mock relay fails, because it lacks the feature
+/// (or, for `PersistsEphemeralEvents` and the like, rightly doesn't do it)
+const REFERENCE_FAILS: &[&str] = &[
+    "ClaimsSupportForNip4",
+    "ClaimsSupportForNip26",
+    "ClaimsSupportForNip29",
+    "ClaimsSupportForNip59",
+    "ClaimsSupportForNip65",
+    "ClaimsSupportForNip94",
+    "ClaimsSupportForNip96",
+    "AcceptsUnlistedJsonEscapeSequences",
+    "AcceptsLiteralsForJsonEscapeSequences",
+    "AcceptsEventsWithCreatedAtInScientificNotation",
+    "ReplacedEventsStillAvailableById",
+    "PersistsEphemeralEvents",
+];
+
+/// Tests the well-behaved mock relay gives no verdict on, because they
+/// probe optional behaviour it lacks (or behaviour it doesn't need)
+const REFERENCE_SKIPPED: &[&str] = &[
+    "CountHllIsWellFormed",
+    "ClosesUnsupportedCount",
+    "ClosesUnsupportedSearch",
+    "Nip11SelfIsHex",
+    "Nip11IconIsUrl",
+    "Nip11BannerIsUrl",
+    "Nip11FeesAreWellFormed",
+    "LimitMinPowDifficulty",
+    "LimitCreatedAtLowerLimit",
+    "LimitCreatedAtUpperLimit",
+    "MaxSubscriptions",
+];
+
+/// Run the selected tests (all if `only` is empty) against a mock relay,
+/// returning the status of each test that ran
+async fn run(mock_config: MockConfig, only: &str) -> BTreeMap<String, String> {
+    let relay = MockRelay::start(mock_config).await.unwrap();
+
+    let config = Config {
+        relay_url: Some(relay.url()),
+        nsec1: Some(PrivateKey::generate().as_bech32_string()),
+        nsec2: Some(PrivateKey::generate().as_bech32_string()),
+        timeouts: Timeouts {
+            wait_ms: 200,
+            reconnect_delay_ms: 10,
+            connect_ms: 2000,
+            handshake_settle_ms: 10,
+            auth_ms: 200,
+            nip11_ms: 2000,
+            idle_ms: 500,
+        },
+        ..Default::default()
+    };
+
+    let registry = Registry::builtin();
+    let selection = Selection {
+        only: Selection::parse_list(only, &registry).unwrap(),
+        skip: vec![],
+    };
+
+    let run = Runner {
+        config,
+        selection,
+        registry,
+    }
+    .run()
+    .await
+    .unwrap();
+
+    run.results
+        .iter()
+        .map(|(test_item, outcome)| {
+            (
+                test_item.id().to_owned(),
+                outcome.status.as_str().to_owned(),
+            )
+        })
+        .collect()
+}
+
+/// Check the statuses of the named tests, listing every mismatch
+fn assert_statuses(results: &BTreeMap<String, String>, expected: &[(&str, &str)]) {
+    let mismatches: Vec<String> = expected
+        .iter()
+        .filter(|(id, status)| results.get(*id).map(|s| s.as_str()) != Some(*status))
+        .map(|(id, status)| format!("{}: expected {}, got {:?}", id, status, results.get(*id)))
+        .collect();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+#[tokio::test]
+async fn reference_relay() {
+    let results = run(MockConfig::default(), "").await;
+
+    assert_eq!(results.len(), Registry::builtin().iter().count());
+
+    let expected: Vec<(&str, &str)> = results
+        .iter()
+        .filter(|(_, status)| *status != "not_implemented")
+        .map(|(id, _)| {
+            if REFERENCE_FAILS.contains(&id.as_str()) {
+                (id.as_str(), "fail")
+            } else if REFERENCE_SKIPPED.contains(&id.as_str()) {
+                (id.as_str(), "skipped")
+            } else {
+                (id.as_str(), "pass")
+            }
+        })
+        .collect();
+
+    assert_statuses(&results, &expected);
+}
+
+#[tokio::test]
+async fn skipping_signature_verification() {
+    let mock_config = MockConfig {
+        verify_signatures: false,
+        ..Default::default()
+    };
+    let results = run(
+        mock_config,
+        "VerifiesSignatures,BadSignatureReasonIsInvalid",
+    )
+    .await;
+    assert_statuses(
+        &results,
+        &[
+            ("VerifiesSignatures", "fail"),
+            ("BadSignatureReasonIsInvalid", "fail"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn skipping_id_verification() {
+    let mock_config = MockConfig {
+        verify_ids: false,
+        ..Default::default()
+    };
+    let results = run(mock_config, "VerifiesIdHashes,BadIdReasonIsInvalid").await;
+    assert_statuses(
+        &results,
+        &[
+            ("VerifiesIdHashes", "fail"),
+            ("BadIdReasonIsInvalid", "fail"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn no_eose() {
+    let mock_config = MockConfig {
+        send_eose: false,
+        ..Default::default()
+    };
+    let results = run(mock_config, "eose,LimitZero").await;
+    assert_statuses(
+        &results,
+        &[
+            ("SupportsEose", "fail"),
+            ("ClosesCompleteSubscriptionsAfterEose", "fail"),
+            ("KeepsOpenIncompleteSubscriptionsAfterEose", "fail"),
+            ("LimitZero", "fail"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn no_auth() {
+    let mock_config = MockConfig {
+        auth: false,
+        ..Default::default()
+    };
+    let results = run(mock_config, "PromptsForAuthInitially,AuthEventKindVerified").await;
+    assert_statuses(
+        &results,
+        &[
+            ("PromptsForAuthInitially", "fail"),
+            ("AuthEventKindVerified", "skipped"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn no_replacement() {
+    let mock_config = MockConfig {
+        replace_events: false,
+        ..Default::default()
+    };
+    let results = run(
+        mock_config,
+        "ReplacesMetadata,ReplaceableEventRejectedIfFuture,AddressableEventRemovesPrevious,\
+         ResubmittedReplacedEventNotRestored",
+    )
+    .await;
+    assert_statuses(
+        &results,
+        &[
+            ("ReplacesMetadata", "fail"),
+            ("ReplaceableEventRejectedIfFuture", "fail"),
+            ("AddressableEventRemovesPrevious", "fail"),
+            ("ResubmittedReplacedEventNotRestored", "fail"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn ignores_deletions() {
+    let mock_config = MockConfig {
+        honour_deletions: false,
+        ..Default::default()
+    };
+    let results = run(
+        mock_config,
+        "DeleteById,ResubmissionOfDeletedById,DeleteByIdAndAddr,DeleteByIdBeforeEventArrives,\
+         ResubmittedDeletedEventNotRestored,DeleteWithKindTag,DeletingDeletionDoesNotUndelete",
+    )
+    .await;
+    assert_statuses(
+        &results,
+        &[
+            ("DeleteById", "fail"),
+            ("ResubmissionOfDeletedById", "fail"),
+            ("ResubmittedDeletedEventNotRestored", "prerequisite_failed"),
+            ("DeleteByIdAndAddr", "fail"),
+            ("DeleteByIdBeforeEventArrives", "fail"),
+            ("DeleteWithKindTag", "fail"),
+            ("DeletingDeletionDoesNotUndelete", "prerequisite_failed"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn keeps_subscriptions() {
+    let mock_config = MockConfig {
+        replace_subscriptions: false,
+        honour_close: false,
+        ..Default::default()
+    };
+    let results = run(mock_config, "subscriptions").await;
+    assert_statuses(
+        &results,
+        &[
+            ("ReqReplacesSubscription", "fail"),
+            ("CloseStopsEvents", "fail"),
+            ("CloseUnknownSubscription", "pass"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn drops_idle_subscribers() {
+    let mock_config = MockConfig {
+        idle_timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
+    };
+    let results = run(mock_config, "NoTimeoutWhileSubscribed").await;
+    assert_statuses(&results, &[("NoTimeoutWhileSubscribed", "fail")]);
+}
+
+#[tokio::test]
+async fn only_first_filter() {
+    let mock_config = MockConfig {
+        only_first_filter: true,
+        ..Default::default()
+    };
+    let results = run(
+        mock_config,
+        "FindByMultipleFilters,MultipleFiltersDeduplicated,LimitIsPerFilter",
+    )
+    .await;
+    assert_statuses(
+        &results,
+        &[
+            ("FindByMultipleFilters", "fail"),
+            ("MultipleFiltersDeduplicated", "pass"),
+            ("LimitIsPerFilter", "fail"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn stores_duplicates() {
+    let mock_config = MockConfig {
+        store_duplicates: true,
+        ..Default::default()
+    };
+    let results = run(
+        mock_config,
+        "DuplicateStoredOnce,DuplicateReasonIsDuplicate",
+    )
+    .await;
+    assert_statuses(
+        &results,
+        &[
+            ("DuplicateStoredOnce", "fail"),
+            ("DuplicateReasonIsDuplicate", "fail"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn stores_ephemeral_events() {
+    let mock_config = MockConfig {
+        store_ephemeral: true,
+        ..Default::default()
+    };
+    let results = run(mock_config, "PersistsEphemeralEvents").await;
+    assert_statuses(&results, &[("PersistsEphemeralEvents", "pass")]);
+}
+
+#[tokio::test]
+async fn no_count() {
+    let mock_config = MockConfig {
+        count: false,
+        supported_nips: vec![1, 9, 11, 42],
+        ..Default::default()
+    };
+    let results = run(mock_config, "CountMatchesReq,ClosesUnsupportedCount").await;
+    assert_statuses(
+        &results,
+        &[
+            ("CountMatchesReq", "fail"),
+            ("ClosesUnsupportedCount", "pass"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn no_search() {
+    let mock_config = MockConfig {
+        search: false,
+        supported_nips: vec![1, 9, 11, 42, 45],
+        ..Default::default()
+    };
+    let results = run(mock_config, "SearchFindsTerm,ClosesUnsupportedSearch").await;
+    assert_statuses(
+        &results,
+        &[
+            ("SearchFindsTerm", "fail"),
+            ("ClosesUnsupportedSearch", "pass"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn ignores_expiration() {
+    let mock_config = MockConfig {
+        expire_events: false,
+        ..Default::default()
+    };
+    let results = run(
+        mock_config,
+        "ExpiredEventRejectedOrHidden,ExpiringEventServedBeforeExpiry,ExpiredEventNotServedById",
+    )
+    .await;
+    assert_statuses(
+        &results,
+        &[
+            ("ExpiredEventRejectedOrHidden", "fail"),
+            ("ExpiringEventServedBeforeExpiry", "pass"),
+            ("ExpiredEventNotServedById", "fail"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn nip11_without_cors() {
+    let mock_config = MockConfig {
+        nip11_cors: false,
+        ..Default::default()
+    };
+    let results = run(
+        mock_config,
+        "Nip11AllowsOrigin,Nip11AllowsHeaders,Nip11AllowsMethods",
+    )
+    .await;
+    assert_statuses(
+        &results,
+        &[
+            ("Nip11AllowsOrigin", "fail"),
+            ("Nip11AllowsHeaders", "fail"),
+            ("Nip11AllowsMethods", "fail"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn malformed_nip11() {
+    let extra = serde_json::json!({
+        "pubkey": "npub1notahexkey",
+        "supported_nips": [1, "11"],
+        "limitation": { "max_limit": "500" },
+        "fees": { "admission": [{ "amount": 1000 }] },
+        "icon": "icon.png",
+        "softwre": "misspelt",
+    });
+    let mock_config = MockConfig {
+        nip11_extra: extra.as_object().unwrap().clone(),
+        ..Default::default()
+    };
+    let results = run(mock_config, "nip11_schema").await;
+    assert_statuses(
+        &results,
+        &[
+            ("Nip11PubkeyIsHex", "fail"),
+            ("Nip11SupportedNipsAreIntegers", "fail"),
+            ("Nip11LimitationIsWellFormed", "fail"),
+            ("Nip11FeesAreWellFormed", "fail"),
+            ("Nip11IconIsUrl", "fail"),
+            ("Nip11HasNoUnknownKeys", "fail"),
+            ("Nip11NameIsString", "pass"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn limits_not_enforced() {
+    let mock_config = MockConfig {
+        enforce_limits: false,
+        ..Default::default()
+    };
+    let results = run(mock_config, "limits").await;
+    assert_statuses(
+        &results,
+        &[
+            ("LimitMaxMessageLength", "fail"),
+            ("LimitMaxSubscriptions", "fail"),
+            ("LimitMaxFilters", "fail"),
+            ("LimitMaxLimit", "fail"),
+            ("LimitMaxSubidLength", "fail"),
+            ("LimitMaxEventTags", "fail"),
+            ("LimitMaxContentLength", "fail"),
+            ("LimitAuthRequired", "pass"),
+            ("LimitRestrictedWrites", "pass"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn serves_private_events_to_anyone_authenticated() {
+    let mock_config = MockConfig {
+        check_recipients: false,
+        ..Default::default()
+    };
+    let results = run(
+        mock_config,
+        "GiftwrapsRequireAuth,Nip4DmsRequireAuth,SecondAuthOnConnection",
+    )
+    .await;
+    assert_statuses(
+        &results,
+        &[
+            ("GiftwrapsRequireAuth", "fail"),
+            ("Nip4DmsRequireAuth", "fail"),
+            ("SecondAuthOnConnection", "pass"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn accepts_dms_without_auth() {
+    let mock_config = MockConfig {
+        protect_dm_writes: false,
+        ..Default::default()
+    };
+    let results = run(mock_config, "Nip4DmWritesRequireAuth").await;
+    assert_statuses(&results, &[("Nip4DmWritesRequireAuth", "fail")]);
+}
+
+#[tokio::test]
+async fn refuses_connections() {
+    let mock_config = MockConfig {
+        max_connections: Some(4),
+        ..Default::default()
+    };
+    let results = run(mock_config, "MaxConnections,ServesPostEoseEvents").await;
+    assert_statuses(
+        &results,
+        &[("MaxConnections", "pass"), ("ServesPostEoseEvents", "pass")],
+    );
+}
+
+#[tokio::test]
+async fn unadvertised_subscription_limit() {
+    let mock_config = MockConfig {
+        limits: MockLimits {
+            max_subscriptions: None,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let results = run(mock_config, "MaxSubscriptions,LimitMaxSubscriptions").await;
+    assert_statuses(
+        &results,
+        &[
+            ("MaxSubscriptions", "pass"),
+            ("LimitMaxSubscriptions", "skipped"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn pow_and_time_limits() {
+    let mock_config = MockConfig {
+        limits: MockLimits {
+            min_pow_difficulty: Some(8),
+            created_at_lower_limit: Some(3600),
+            created_at_upper_limit: Some(900),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let results = run(
+        mock_config,
+        "LimitMinPowDifficulty,LimitCreatedAtLowerLimit,LimitCreatedAtUpperLimit",
+    )
+    .await;
+    assert_statuses(
+        &results,
+        &[
+            ("LimitMinPowDifficulty", "pass"),
+            ("LimitCreatedAtLowerLimit", "pass"),
+            ("LimitCreatedAtUpperLimit", "pass"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn auth_required_and_restricted_writes() {
+    let mock_config = MockConfig {
+        auth_required: true,
+        restricted_writes: true,
+        ..Default::default()
+    };
+    let results = run(mock_config, "LimitAuthRequired,LimitRestrictedWrites").await;
+    assert_statuses(
+        &results,
+        &[
+            ("LimitAuthRequired", "pass"),
+            ("LimitRestrictedWrites", "pass"),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn serves_giftwraps_to_anyone() {
+    let mock_config = MockConfig {
+        protect_giftwraps: false,
+        ..Default::default()
+    };
+    let results = run(mock_config, "reasons").await;
+    assert_statuses(
+        &results,
+        &[
+            ("BadSignatureReasonIsInvalid", "pass"),
+            ("BadIdReasonIsInvalid", "pass"),
+            ("DuplicateReasonIsDuplicate", "pass"),
+            ("GiftwrapReqClosedWithAuthRequired", "fail"),
+        ],
+    );
+}